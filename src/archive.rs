@@ -0,0 +1,697 @@
+use std::cmp::Ordering;
+use std::io::{BufReader, Read};
+use std::sync::{Arc, Mutex};
+use crate::zimheader::ZimHeader;
+use crate::zimfile::ZimFile;
+use crate::cluster::{self, Cluster, Compression};
+use crate::blob_reader::BlobReader;
+use crate::entries::{Entries, EntryOrder};
+use crate::dirent::{Dirent, DirentData};
+use crate::search::{binary_search_by, title_prefix_ranges};
+use crate::md5::Md5;
+use crate::redirect::{self, DEFAULT_MAX_REDIRECTS, MAIN_PAGE_NAMESPACE, MAIN_PAGE_PATH};
+use crate::error::{Structure, ZimError};
+use crate::cache::{CacheConfig, CacheStats, ClusterCache};
+use crate::read_at::{ReadAt, ReadAtCursor};
+
+const CHECKSUM_CHUNK_SIZE: usize = 64 * 1024;
+const DIRENT_BUFFER_SIZE: usize = 256;
+
+/// Lazy handle on a zim file.
+///
+/// Unlike `ZimFile`, only the header and the mime list are read upfront.
+/// Pointers, dirents and clusters are read from the underlying reader on demand,
+/// which keeps opening large archives cheap.
+/// Recently used compressed clusters are kept in a cache, see `CacheConfig`.
+///
+/// All reads are positional (see `ReadAt`), so an `Archive<File>` is `Send + Sync`
+/// and can be shared between threads reading entries concurrently.
+/// Use `SeekReader` to wrap a source that can only `Read + Seek`.
+#[derive(Debug)]
+pub struct Archive<R: ReadAt> {
+    reader: R,
+    cache: Mutex<ClusterCache>,
+    pub header: ZimHeader,
+    pub mime_types: Vec<String>,
+}
+
+impl<R: ReadAt> Archive<R> {
+    pub fn new(reader: R) -> Result<Self, ZimError> {
+        Archive::with_cache_config(reader, CacheConfig::default())
+    }
+
+    pub fn with_cache_config(reader: R, cache_config: CacheConfig) -> Result<Self, ZimError> {
+        let mut cursor = ReadAtCursor::new(&reader, 0);
+        let header = ZimHeader::parse_header(&mut cursor)?;
+        let mime_types = ZimFile::parse_mime_types(&mut cursor, &header)?;
+        let cache = Mutex::new(ClusterCache::new(cache_config));
+        Ok(Archive { reader, cache, header, mime_types })
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.lock_cache().stats()
+    }
+
+    pub fn entry_count(&self) -> u32 {
+        self.header.article_count
+    }
+
+    pub fn cluster_count(&self) -> u32 {
+        self.header.cluster_count
+    }
+
+    pub fn get_dirent_pointer(&self, index: u32) -> Result<u64, ZimError> {
+        if index >= self.header.article_count {
+            return Err(ZimError::OutOfBounds {
+                structure: Structure::PathPointerList,
+                index: index as u64,
+                count: self.header.article_count as u64,
+            });
+        }
+        self.read_u64_at(Structure::PathPointerList, self.header.path_ptr_pos + 8 * index as u64)
+    }
+
+    pub fn get_dirent(&self, index: u32) -> Result<Dirent, ZimError> {
+        let offset = self.get_dirent_pointer(index)?;
+        // Dirents are read byte by byte, buffer to avoid one positional read per byte.
+        let reader = BufReader::with_capacity(DIRENT_BUFFER_SIZE, ReadAtCursor::new(&self.reader, offset));
+        Dirent::parse(reader).map_err(|e| e.at(offset))
+    }
+
+    pub fn get_cluster_pointer(&self, cluster_number: u32) -> Result<u64, ZimError> {
+        if cluster_number >= self.header.cluster_count {
+            return Err(ZimError::OutOfBounds {
+                structure: Structure::ClusterPointerList,
+                index: cluster_number as u64,
+                count: self.header.cluster_count as u64,
+            });
+        }
+        self.read_u64_at(Structure::ClusterPointerList, self.header.cluster_ptr_pos + 8 * cluster_number as u64)
+    }
+
+    pub fn get_cluster(&self, cluster_number: u32) -> Result<Arc<Cluster>, ZimError> {
+        if let Some(cluster) = self.lock_cache().get(cluster_number) {
+            return Ok(cluster);
+        }
+        // The cache isn't locked while reading, so other threads aren't blocked on I/O.
+        let cluster = Arc::new(self.read_cluster(cluster_number)?);
+        self.lock_cache().insert(cluster_number, Arc::clone(&cluster));
+        Ok(cluster)
+    }
+
+    fn read_cluster(&self, cluster_number: u32) -> Result<Cluster, ZimError> {
+        let (offset, size) = self.get_cluster_range(cluster_number)?;
+        let reader = BufReader::new(ReadAtCursor::new(&self.reader, offset).take(size));
+        Cluster::parse(reader).map_err(|e| e.at(offset))
+    }
+
+    /// Offset and size of a cluster, the size is `u64::MAX` when it can't be determined.
+    fn get_cluster_range(&self, cluster_number: u32) -> Result<(u64, u64), ZimError> {
+        let offset = self.get_cluster_pointer(cluster_number)?;
+        // Same bounding as `ZimFile::parse_clusters`: a cluster ends where the next one starts.
+        let end = if cluster_number + 1 < self.header.cluster_count {
+            Some(self.get_cluster_pointer(cluster_number + 1)?)
+        } else if self.header.has_checksum() {
+            Some(self.header.checksum_pos)
+        } else {
+            None
+        };
+        let size = match end {
+            Some(end) if end > offset => end - offset,
+            _ => u64::MAX,
+        };
+        Ok((offset, size))
+    }
+
+    /// Reads the blob of `dirent`.
+    ///
+    /// Blobs of uncompressed clusters are read directly, only compressed clusters go
+    /// through the cache, so large uncompressed clusters don't push them out of it.
+    pub fn get_blob(&self, dirent: &Dirent) -> Result<Vec<u8>, ZimError> {
+        let (cluster_number, blob_number) = match dirent.data {
+            DirentData::Content { cluster_number, blob_number } => (cluster_number, blob_number),
+            _ => return Err(ZimError::NoContent { path: dirent.url.clone() }),
+        };
+
+        let (offset, size) = self.get_cluster_range(cluster_number)?;
+        let (compression, is_extended) = cluster::read_info(&mut ReadAtCursor::new(&self.reader, offset))
+            .map_err(|e| e.at(offset))?;
+        if compression == Compression::None {
+            let mut blob = BlobReader::uncompressed(&self.reader, offset, size, is_extended, blob_number)
+                .map_err(|e| e.at(offset))?;
+            let mut data = Vec::new();
+            blob.read_to_end(&mut data).map_err(|e| ZimError::io(Structure::Cluster, e).at(offset))?;
+            return Ok(data);
+        }
+
+        let cluster = self.get_cluster(cluster_number)?;
+        cluster.get_blob(blob_number as usize)
+            .map(|b| b.to_vec())
+            .ok_or(ZimError::OutOfBounds {
+                structure: Structure::Cluster,
+                index: blob_number as u64,
+                count: cluster.count() as u64,
+            })
+    }
+
+    /// Streams the blob of `dirent` instead of reading it into memory, see `BlobReader`.
+    pub fn blob_reader(&self, dirent: &Dirent) -> Result<BlobReader<'_, R>, ZimError> {
+        let (cluster_number, blob_number) = match dirent.data {
+            DirentData::Content { cluster_number, blob_number } => (cluster_number, blob_number),
+            _ => return Err(ZimError::NoContent { path: dirent.url.clone() }),
+        };
+
+        let (offset, size) = self.get_cluster_range(cluster_number)?;
+        let mut reader = BufReader::new(ReadAtCursor::new(&self.reader, offset).take(size));
+        let (compression, is_extended) = cluster::read_info(&mut reader).map_err(|e| e.at(offset))?;
+        match compression {
+            Compression::None => BlobReader::uncompressed(&self.reader, offset, size, is_extended, blob_number),
+            // Decompressing a cached cluster again would be wasted work.
+            Compression::Zstd => match self.lock_cache().get(cluster_number) {
+                Some(cluster) => BlobReader::cached(cluster, blob_number),
+                None => BlobReader::zstd(reader, is_extended, blob_number),
+            },
+            _ => BlobReader::cached(self.get_cluster(cluster_number)?, blob_number),
+        }
+        .map_err(|e| e.at(offset))
+    }
+
+    /// Follows redirects from `dirent` to the entry they finally point to.
+    pub fn resolve_redirect(&self, dirent: Dirent) -> Result<Dirent, ZimError> {
+        self.resolve_redirect_with_depth(dirent, DEFAULT_MAX_REDIRECTS)
+    }
+
+    /// Like `resolve_redirect`, following at most `max_depth` redirects.
+    pub fn resolve_redirect_with_depth(&self, dirent: Dirent, max_depth: usize) -> Result<Dirent, ZimError> {
+        let count = self.header.article_count;
+        redirect::resolve(dirent, count, max_depth, |i| self.get_dirent(i))
+    }
+
+    /// The main page of the archive with redirects followed.
+    ///
+    /// Uses `header.main_page` and falls back to the `W/mainPage` entry.
+    /// Returns `None` if the archive has neither.
+    pub fn main_entry(&self) -> Result<Option<Dirent>, ZimError> {
+        let start = match self.header.main_page_index() {
+            Some(index) => self.get_dirent(index)?,
+            None => match self.find_by_path(MAIN_PAGE_NAMESPACE, MAIN_PAGE_PATH)? {
+                Some((_, dirent)) => dirent,
+                None => return Ok(None),
+            },
+        };
+        self.resolve_redirect(start).map(Some)
+    }
+
+    /// Looks up an entry by namespace and path.
+    ///
+    /// The path pointer list is sorted by namespace and path, so this is a binary search
+    /// that only reads the dirents it probes.
+    pub fn find_by_path(&self, namespace: char, path: &str) -> Result<Option<(u32, Dirent)>, ZimError> {
+        let mut found = None;
+        let result = binary_search_by(self.header.article_count, |index| {
+            let dirent = self.get_dirent(index)?;
+            let ordering = dirent.cmp_path(namespace, path);
+            if ordering == Ordering::Equal {
+                found = Some(dirent);
+            }
+            Ok(ordering)
+        })?;
+        Ok(result.ok().zip(found))
+    }
+
+    /// Looks up a content entry by path, in 'C' or in the legacy content namespaces
+    /// depending on the namespace scheme of the archive.
+    pub fn find_content(&self, path: &str) -> Result<Option<(u32, Dirent)>, ZimError> {
+        for &namespace in self.header.namespace_scheme().content_namespaces() {
+            if let Some(found) = self.find_by_path(namespace, path)? {
+                return Ok(Some(found));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the position-th entry of the title index, i.e. the index of the
+    /// dirent that comes at that position in title order.
+    pub fn get_title_index_entry(&self, position: u32) -> Result<u32, ZimError> {
+        if !self.header.has_title_index() {
+            return Err(ZimError::MissingTitleIndex);
+        }
+        let count = self.header.article_count as u64;
+        if position as u64 >= count {
+            return Err(ZimError::OutOfBounds { structure: Structure::TitlePointerList, index: position as u64, count });
+        }
+        let index = self.read_u32_at(Structure::TitlePointerList, self.header.title_idx_pos + 4 * position as u64)?;
+        if index as u64 >= count {
+            return Err(ZimError::OutOfBounds { structure: Structure::PathPointerList, index: index as u64, count });
+        }
+        Ok(index)
+    }
+
+    /// Looks up an entry by namespace and title with a binary search over the title index.
+    /// Like `ZimFile::find_by_title`, finds nothing in archives without a title index.
+    pub fn find_by_title(&self, namespace: char, title: &str) -> Result<Option<(u32, Dirent)>, ZimError> {
+        if !self.header.has_title_index() {
+            return Ok(None);
+        }
+        let mut found = None;
+        let result = binary_search_by(self.header.article_count, |position| {
+            let index = self.get_title_index_entry(position)?;
+            let dirent = self.get_dirent(index)?;
+            let ordering = dirent.cmp_title(namespace, title);
+            if ordering == Ordering::Equal {
+                found = Some((index, dirent));
+            }
+            Ok(ordering)
+        })?;
+        Ok(result.ok().and(found))
+    }
+
+    /// First `limit` entries of `namespace` whose title starts with `prefix`, in title order.
+    /// Meant for type-ahead suggestions: the matches are found with binary searches over the title index.
+    pub fn find_by_title_prefix(&self, namespace: char, prefix: &str, limit: usize) -> Result<Vec<(u32, Dirent)>, ZimError> {
+        self.title_prefix_matches(namespace, prefix, limit, false)
+    }
+
+    /// Like `find_by_title_prefix`, ignoring case.
+    pub fn find_by_title_prefix_ignore_case(&self, namespace: char, prefix: &str, limit: usize) -> Result<Vec<(u32, Dirent)>, ZimError> {
+        self.title_prefix_matches(namespace, prefix, limit, true)
+    }
+
+    fn title_prefix_matches(&self, namespace: char, prefix: &str, limit: usize, ignore_case: bool) -> Result<Vec<(u32, Dirent)>, ZimError> {
+        if !self.header.has_title_index() {
+            return Ok(Vec::new());
+        }
+        let ranges = title_prefix_ranges(self.header.article_count, namespace, prefix, ignore_case, |position| {
+            self.get_dirent(self.get_title_index_entry(position)?)
+        })?;
+
+        let mut matches = Vec::new();
+        for position in ranges.into_iter().flatten().take(limit) {
+            let index = self.get_title_index_entry(position)?;
+            matches.push((index, self.get_dirent(index)?));
+        }
+        Ok(matches)
+    }
+
+    /// Lazily iterates over the entries in path order.
+    pub fn entries_by_path(&self) -> Entries<'_, R> {
+        Entries::new(self, EntryOrder::Path)
+    }
+
+    /// Lazily iterates over the entries in title order, following the title index.
+    pub fn entries_by_title(&self) -> Result<Entries<'_, R>, ZimError> {
+        if !self.header.has_title_index() {
+            return Err(ZimError::MissingTitleIndex);
+        }
+        Ok(Entries::new(self, EntryOrder::Title))
+    }
+
+    /// Computes the MD5 of the file up to `header.checksum_pos` and compares it
+    /// to the 16 bytes stored there. Fails with `MissingChecksum` for archives without one.
+    pub fn verify_checksum(&self) -> Result<bool, ZimError> {
+        self.verify_checksum_with_progress(|_, _| {})
+    }
+
+    /// Like `verify_checksum`, calling `progress(bytes_hashed, total_bytes)` after each chunk.
+    pub fn verify_checksum_with_progress(&self, mut progress: impl FnMut(u64, u64)) -> Result<bool, ZimError> {
+        if !self.header.has_checksum() {
+            return Err(ZimError::MissingChecksum);
+        }
+        let total = self.header.checksum_pos;
+
+        let io_error = |e| ZimError::io(Structure::Checksum, e).at(total);
+        let mut hasher = Md5::new();
+        let mut buffer = vec![0u8; CHECKSUM_CHUNK_SIZE];
+        let mut done = 0u64;
+        while done < total {
+            let n = std::cmp::min(CHECKSUM_CHUNK_SIZE as u64, total - done) as usize;
+            self.reader.read_exact_at(&mut buffer[..n], done).map_err(io_error)?;
+            hasher.update(&buffer[..n]);
+            done += n as u64;
+            progress(done, total);
+        }
+
+        let mut stored = [0u8; 16];
+        self.reader.read_exact_at(&mut stored, total).map_err(io_error)?;
+        Ok(hasher.finalize() == stored)
+    }
+
+    fn read_u32_at(&self, structure: Structure, pos: u64) -> Result<u32, ZimError> {
+        let mut buffer = [0u8; 4];
+        self.read_exact_at(structure, pos, &mut buffer)?;
+        Ok(u32::from_le_bytes(buffer))
+    }
+
+    fn read_u64_at(&self, structure: Structure, pos: u64) -> Result<u64, ZimError> {
+        let mut buffer = [0u8; 8];
+        self.read_exact_at(structure, pos, &mut buffer)?;
+        Ok(u64::from_le_bytes(buffer))
+    }
+
+    fn read_exact_at(&self, structure: Structure, pos: u64, buffer: &mut [u8]) -> Result<(), ZimError> {
+        self.reader.read_exact_at(buffer, pos).map_err(|e| ZimError::io(structure, e).at(pos))
+    }
+
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, ClusterCache> {
+        // The cache holds no invariant a panicking thread could break, keep using it.
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::test_util::{blob, build_zim, redirect, remove_checksum};
+    use crate::zimheader::NO_MAIN_PAGE;
+
+    fn test_archive() -> Archive<Cursor<Vec<u8>>> {
+        let data = build_zim(&[
+            blob('C', "a", "A", b"first"),
+            blob('C', "b", "B", b"second"),
+            redirect('C', "c", "C", 0),
+        ], 0);
+        Archive::new(Cursor::new(data)).expect("Failed to open archive")
+    }
+
+    #[test]
+    fn test_open_reads_header_and_mime_types() {
+        let archive = test_archive();
+        assert_eq!(archive.entry_count(), 3);
+        assert_eq!(archive.cluster_count(), 1);
+        assert_eq!(archive.mime_types, vec!["text/html"]);
+    }
+
+    #[test]
+    fn test_get_dirent_and_blob() {
+        let archive = test_archive();
+
+        let d1 = archive.get_dirent(1).unwrap();
+        assert_eq!(d1.url, "b");
+        assert_eq!(archive.get_blob(&d1).unwrap(), b"second");
+
+        let d0 = archive.get_dirent(0).unwrap();
+        assert_eq!(archive.get_blob(&d0).unwrap(), b"first");
+
+        let d2 = archive.get_dirent(2).unwrap();
+        assert!(d2.is_redirect());
+        assert!(archive.get_blob(&d2).is_err());
+
+        let missing = Dirent { data: DirentData::Content { cluster_number: 0, blob_number: 5 }, ..d0 };
+        assert!(matches!(archive.get_blob(&missing), Err(ZimError::OutOfBounds { structure: Structure::Cluster, .. })));
+    }
+
+    #[test]
+    fn test_blob_reader() {
+        let archive = test_archive();
+        let d1 = archive.get_dirent(1).unwrap();
+        let mut out = Vec::new();
+        std::io::copy(&mut archive.blob_reader(&d1).unwrap(), &mut out).unwrap();
+        assert_eq!(out, archive.get_blob(&d1).unwrap());
+
+        let d2 = archive.get_dirent(2).unwrap();
+        assert!(matches!(archive.blob_reader(&d2), Err(ZimError::NoContent { .. })));
+    }
+
+    #[test]
+    fn test_cluster_cache() {
+        let mut writer = crate::writer::ZimWriter::new();
+        writer.add_item("a", "", "text/plain", "first");
+        writer.add_item("b", "", "text/plain", "second");
+        let data = writer.to_bytes().unwrap();
+
+        let archive = Archive::new(Cursor::new(data.clone())).unwrap();
+        let d0 = archive.get_dirent(0).unwrap();
+        let d1 = archive.get_dirent(1).unwrap();
+        assert_eq!(archive.get_blob(&d0).unwrap(), b"first");
+        assert_eq!(archive.get_blob(&d1).unwrap(), b"second");
+        archive.get_blob(&d0).unwrap();
+        let stats = archive.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.clusters), (2, 1, 1));
+
+        let config = CacheConfig { max_clusters: 0, ..CacheConfig::default() };
+        let uncached = Archive::with_cache_config(Cursor::new(data), config).unwrap();
+        let dirent = uncached.get_dirent(0).unwrap();
+        uncached.get_blob(&dirent).unwrap();
+        uncached.get_blob(&dirent).unwrap();
+        assert_eq!(uncached.cache_stats().misses, 2);
+        assert_eq!(uncached.cache_stats().hits, 0);
+
+        // Blobs of uncompressed clusters are read directly, without the cache
+        let archive = test_archive();
+        assert_eq!(archive.get_blob(&archive.get_dirent(1).unwrap()).unwrap(), b"second");
+        let stats = archive.cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.clusters), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_out_of_range_indices() {
+        let archive = test_archive();
+        assert!(archive.get_dirent(3).is_err());
+        assert!(archive.get_cluster(1).is_err());
+    }
+
+    #[test]
+    fn test_resolve_redirect() {
+        let data = build_zim(&[
+            redirect('C', "a", "", 1),
+            redirect('C', "b", "", 3),
+            redirect('C', "c", "", 2),
+            blob('C', "d", "", b"target"),
+        ], 0);
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+
+        let start = archive.get_dirent(0).unwrap();
+        let target = archive.resolve_redirect(start).unwrap();
+        assert_eq!(target.url, "d");
+        assert_eq!(archive.get_blob(&target).unwrap(), b"target");
+
+        let looping = archive.get_dirent(2).unwrap();
+        assert!(matches!(archive.resolve_redirect(looping), Err(ZimError::RedirectLoop { index: 2 })));
+
+        let start = archive.get_dirent(0).unwrap();
+        assert!(matches!(archive.resolve_redirect_with_depth(start, 1), Err(ZimError::RedirectTooDeep { max_depth: 1 })));
+    }
+
+    #[test]
+    fn test_main_entry() {
+        let entries = [
+            blob('C', "index", "", b"home"),
+            redirect('W', "mainPage", "", 0),
+        ];
+
+        let archive = Archive::new(Cursor::new(build_zim(&entries, 0))).unwrap();
+        assert_eq!(archive.main_entry().unwrap().unwrap().url, "index");
+
+        let archive = Archive::new(Cursor::new(build_zim(&entries, NO_MAIN_PAGE))).unwrap();
+        assert_eq!(archive.main_entry().unwrap().unwrap().url, "index");
+
+        let archive = Archive::new(Cursor::new(build_zim(&entries[..1], NO_MAIN_PAGE))).unwrap();
+        assert!(archive.main_entry().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let data = build_zim(&[blob('C', "a", "", b"some content")], 0);
+        let archive = Archive::new(Cursor::new(data.clone())).unwrap();
+
+        let mut calls = Vec::new();
+        assert!(archive.verify_checksum_with_progress(|done, total| calls.push((done, total))).unwrap());
+        let total = archive.header.checksum_pos;
+        assert_eq!(calls.last(), Some(&(total, total)));
+
+        // Flip one byte of blob data
+        let mut corrupted = data;
+        corrupted[total as usize - 1] ^= 0xff;
+        let archive = Archive::new(Cursor::new(corrupted)).unwrap();
+        assert!(!archive.verify_checksum().unwrap());
+    }
+
+    #[test]
+    fn test_verify_checksum_missing() {
+        let mut data = build_zim(&[blob('C', "a", "", b"x")], 0);
+        data.truncate(data.len() - 8);
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+        assert!(archive.verify_checksum().is_err());
+    }
+
+    #[test]
+    fn test_unsupported_compression() {
+        let mut data = build_zim(&[blob('C', "a", "", b"zipped")], 0);
+        let cluster_ptr_pos = ZimHeader::parse_header(&mut &data[..]).unwrap().cluster_ptr_pos as usize;
+        let cluster_pos = u64::from_le_bytes(data[cluster_ptr_pos..cluster_ptr_pos + 8].try_into().unwrap());
+        data[cluster_pos as usize] = Compression::Zip as u8;
+
+        let archive = Archive::new(Cursor::new(data.clone())).unwrap();
+        let (_, dirent) = archive.find_by_path('C', "a").unwrap().unwrap();
+        let unsupported = |result: Result<(), ZimError>| matches!(result, Err(ZimError::UnsupportedCompression { compression: Compression::Zip }));
+        assert!(unsupported(archive.get_blob(&dirent).map(|_| ())));
+        assert!(unsupported(archive.blob_reader(&dirent).map(|_| ())));
+        assert!(unsupported(crate::zimfile::ZimFile::parse_bytes(&mut Cursor::new(data)).map(|_| ())));
+    }
+
+    #[test]
+    fn test_title_lookups_without_title_index() {
+        let mut data = build_zim(&[blob('C', "a", "Title", b"x")], 0);
+        data[40..48].copy_from_slice(&u64::MAX.to_le_bytes());
+        let archive = Archive::new(Cursor::new(data.clone())).unwrap();
+        let zim = crate::zimfile::ZimFile::parse_bytes(&mut Cursor::new(data)).unwrap();
+
+        assert!(archive.find_by_title('C', "Title").unwrap().is_none());
+        assert!(zim.find_by_title('C', "Title").is_none());
+        assert!(archive.find_by_title_prefix('C', "T", 10).unwrap().is_empty());
+        assert!(zim.find_by_title_prefix('C', "T", 10).is_empty());
+        assert!(archive.find_by_title_prefix_ignore_case('C', "t", 10).unwrap().is_empty());
+        assert!(zim.find_by_title_prefix_ignore_case('C', "t", 10).is_empty());
+    }
+
+    #[test]
+    fn test_without_checksum() {
+        let data = remove_checksum(&build_zim(&[blob('C', "a", "", b"first"), blob('C', "b", "", b"last")], 0));
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+        assert!(!archive.header.has_checksum());
+        assert!(matches!(archive.verify_checksum(), Err(ZimError::MissingChecksum)));
+
+        // The last cluster is read up to the end of the file
+        let (_, dirent) = archive.find_by_path('C', "b").unwrap().unwrap();
+        assert_eq!(archive.get_blob(&dirent).unwrap(), b"last");
+    }
+
+    #[test]
+    fn test_find_by_path() {
+        let data = build_zim(&[
+            blob('A', "zebra", "", b"0"),
+            blob('C', "Apple", "", b"1"),
+            blob('C', "apple", "", b"2"),
+            blob('C', "banana", "", b"3"),
+            blob('C', "banana/split", "", b"4"),
+            blob('M', "Title", "", b"5"),
+        ], 0);
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+
+        let (index, dirent) = archive.find_by_path('C', "banana").unwrap().unwrap();
+        assert_eq!(index, 3);
+        assert_eq!(dirent.url, "banana");
+        assert_eq!(archive.get_blob(&dirent).unwrap(), b"3");
+
+        assert_eq!(archive.find_by_path('A', "zebra").unwrap().unwrap().0, 0);
+        assert_eq!(archive.find_by_path('C', "Apple").unwrap().unwrap().0, 1);
+        assert_eq!(archive.find_by_path('M', "Title").unwrap().unwrap().0, 5);
+        assert!(archive.find_by_path('C', "zebra").unwrap().is_none());
+        assert!(archive.find_by_path('C', "bananas").unwrap().is_none());
+        assert!(archive.find_by_path('X', "Title").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_find_content() {
+        let entries = [
+            blob('A', "Foo", "", b"old"),
+            blob('C', "Foo", "", b"new"),
+            blob('I', "logo.png", "", b"png"),
+        ];
+        let archive = Archive::new(Cursor::new(build_zim(&entries, 0))).unwrap();
+        let (_, dirent) = archive.find_content("Foo").unwrap().unwrap();
+        assert_eq!(archive.get_blob(&dirent).unwrap(), b"new");
+        assert!(archive.find_content("logo.png").unwrap().is_none());
+
+        let mut data = build_zim(&entries, 0);
+        crate::test_util::set_version(&mut data, 5, 0);
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+        let (_, dirent) = archive.find_content("Foo").unwrap().unwrap();
+        assert_eq!(archive.get_blob(&dirent).unwrap(), b"old");
+        assert_eq!(archive.find_content("logo.png").unwrap().unwrap().0, 2);
+    }
+
+    #[test]
+    fn test_find_by_title() {
+        let data = build_zim(&[
+            blob('C', "cherry", "Zesty", b"0"),
+            blob('C', "date", "", b"1"),
+            blob('C', "fig", "Apple pie", b"2"),
+            blob('M', "Title", "Fruit", b"3"),
+        ], 0);
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+
+        assert_eq!(archive.get_title_index_entry(0).unwrap(), 2);
+        assert_eq!(archive.get_title_index_entry(1).unwrap(), 0);
+        assert_eq!(archive.get_title_index_entry(2).unwrap(), 1);
+
+        let (index, dirent) = archive.find_by_title('C', "Zesty").unwrap().unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(dirent.url, "cherry");
+        // Entries without a title are indexed by their path
+        assert_eq!(archive.find_by_title('C', "date").unwrap().unwrap().0, 1);
+        assert_eq!(archive.find_by_title('M', "Fruit").unwrap().unwrap().0, 3);
+        assert!(archive.find_by_title('C', "cherry").unwrap().is_none());
+        assert!(archive.find_by_title('C', "Fruit").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_find_by_title_prefix() {
+        let data = build_zim(&[
+            blob('C', "a", "Paris", b""),
+            blob('C', "b", "parrot", b""),
+            blob('C', "c", "Pasta", b""),
+            blob('C', "d", "Lyon", b""),
+            blob('M', "Title", "Paris guide", b""),
+        ], 0);
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+        let titles = |matches: Vec<(u32, Dirent)>| matches.into_iter().map(|(_, d)| d.get_title().to_string()).collect::<Vec<_>>();
+
+        assert_eq!(titles(archive.find_by_title_prefix('C', "Pa", 10).unwrap()), vec!["Paris", "Pasta"]);
+        assert_eq!(titles(archive.find_by_title_prefix('C', "Pa", 1).unwrap()), vec!["Paris"]);
+        assert_eq!(titles(archive.find_by_title_prefix_ignore_case('C', "par", 10).unwrap()), vec!["Paris", "parrot"]);
+        assert!(archive.find_by_title_prefix('C', "par", 0).unwrap().is_empty());
+        assert!(archive.find_by_title_prefix('C', "Rome", 10).unwrap().is_empty());
+
+        let (index, dirent) = archive.find_by_title_prefix('M', "P", 10).unwrap().remove(0);
+        assert_eq!((index, dirent.url.as_str()), (4, "Title"));
+    }
+
+    #[test]
+    fn test_matches_eager_parsing() {
+        let data = build_zim(&[blob('C', "x", "", b"data")], 0);
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(data.clone())).unwrap();
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+
+        let dirent = archive.get_dirent(0).unwrap();
+        assert_eq!(dirent.url, zim.dirents[0].url);
+        assert_eq!(archive.get_blob(&dirent).unwrap(), zim.get_blob(&zim.dirents[0]).unwrap());
+    }
+
+    #[test]
+    fn test_archive_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Archive<std::fs::File>>();
+        assert_send_sync::<Archive<Vec<u8>>>();
+        assert_send_sync::<Archive<crate::SeekReader<Cursor<Vec<u8>>>>>();
+    }
+
+    #[test]
+    fn test_concurrent_reads() {
+        let entries: Vec<_> = (0..20)
+            .map(|i| blob('C', &format!("{:02}", i), "", format!("content {}", i).as_bytes()))
+            .collect();
+        let data = build_zim(&entries, NO_MAIN_PAGE);
+        let path = std::env::temp_dir().join(format!("libzim-rs-concurrent-{}.zim", std::process::id()));
+        std::fs::write(&path, &data).unwrap();
+        let archive = Archive::with_cache_config(
+            std::fs::File::open(&path).unwrap(),
+            CacheConfig { max_clusters: 1, max_bytes: 1024 },
+        ).unwrap();
+
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let archive = &archive;
+                scope.spawn(move || {
+                    for i in (0..20).map(|i| (i + thread * 5) % 20) {
+                        let (_, dirent) = archive.find_by_path('C', &format!("{:02}", i)).unwrap().unwrap();
+                        assert_eq!(archive.get_blob(&dirent).unwrap(), format!("content {}", i).as_bytes());
+                    }
+                });
+            }
+        });
+        assert!(archive.verify_checksum().unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
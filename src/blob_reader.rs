@@ -0,0 +1,233 @@
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Take};
+use std::sync::Arc;
+use ruzstd::decoding::{FrameDecoder, StreamingDecoder};
+use crate::cluster::{self, Cluster, Compression};
+use crate::error::{Structure, ZimError};
+use crate::read_at::{ReadAt, ReadAtCursor};
+
+const TABLE_BUFFER_SIZE: usize = 512;
+
+type ClusterReader<'a, R> = BufReader<Take<ReadAtCursor<'a, R>>>;
+
+/// `Read` over a single blob, returned by `Archive::blob_reader`.
+///
+/// Blobs of uncompressed clusters are read straight from the archive and are fully seekable.
+/// Zstd clusters are decompressed on the fly up to the end of the blob, so memory use doesn't
+/// depend on the blob size; they can only seek forward.
+/// Other compressions go through the decompressed cluster of the archive cache.
+pub struct BlobReader<'a, R: ReadAt> {
+    source: Source<'a, R>,
+    size: u64,
+    pos: u64,
+}
+
+enum Source<'a, R: ReadAt> {
+    /// Blob stored as is, at `start` in the archive.
+    Raw { reader: &'a R, start: u64 },
+    /// Blob of a cluster already decompressed in memory.
+    Cached { cluster: Arc<Cluster>, start: usize },
+    /// Decompressor positioned at the current position in the blob.
+    Zstd(Box<StreamingDecoder<ClusterReader<'a, R>, FrameDecoder>>),
+}
+
+impl<'a, R: ReadAt> BlobReader<'a, R> {
+    /// Blob `index` of the uncompressed cluster at `offset`, of `cluster_size` bytes if known.
+    pub(crate) fn uncompressed(reader: &'a R, offset: u64, cluster_size: u64, is_extended: bool, index: u32) -> Result<Self, ZimError> {
+        let mut table = BufReader::with_capacity(TABLE_BUFFER_SIZE, ReadAtCursor::new(reader, offset + 1));
+        let (start, end) = cluster::read_blob_range(&mut table, is_extended, index)?;
+        // The info byte precedes the offset table.
+        if cluster_size != u64::MAX && end + 1 > cluster_size {
+            return Err(ZimError::TruncatedCluster { offset: 0, expected: end + 1, found: cluster_size });
+        }
+        Ok(BlobReader { source: Source::Raw { reader, start: offset + 1 + start }, size: end - start, pos: 0 })
+    }
+
+    /// Blob `index` of a zstd cluster, `reader` being positioned right after the info byte.
+    pub(crate) fn zstd(reader: ClusterReader<'a, R>, is_extended: bool, index: u32) -> Result<Self, ZimError> {
+        let mut decoder = StreamingDecoder::new(reader)
+            .map_err(|e| cluster::decompression_error(Compression::Zstd, e))?;
+        let (start, end) = cluster::read_blob_range(&mut decoder, is_extended, index)?;
+
+        // Skip the rest of the offset table and the blobs before this one.
+        let offset_size = if is_extended { 8 } else { 4 };
+        let skip = start - (index as u64 + 2) * offset_size;
+        let skipped = skip_bytes(&mut decoder, skip).map_err(|e| ZimError::io(Structure::Cluster, e))?;
+        if skipped < skip {
+            return Err(ZimError::TruncatedCluster { offset: 0, expected: start, found: start - skip + skipped });
+        }
+        Ok(BlobReader { source: Source::Zstd(Box::new(decoder)), size: end - start, pos: 0 })
+    }
+
+    /// Blob `index` of a cluster already read in memory.
+    pub(crate) fn cached(cluster: Arc<Cluster>, index: u32) -> Result<Self, ZimError> {
+        let blob = cluster.get_blob(index as usize).ok_or(ZimError::OutOfBounds {
+            structure: Structure::Cluster,
+            index: index as u64,
+            count: cluster.count() as u64,
+        })?;
+        let start = (cluster.blob_offsets[index as usize] - cluster.blob_offsets[0]) as usize;
+        let size = blob.len() as u64;
+        Ok(BlobReader { source: Source::Cached { cluster, start }, size, pos: 0 })
+    }
+
+    /// Size of the blob in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Whether the reader can seek backwards.
+    pub fn is_seekable(&self) -> bool {
+        !matches!(self.source, Source::Zstd(_))
+    }
+}
+
+impl<R: ReadAt> Read for BlobReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.pos);
+        let len = std::cmp::min(buf.len() as u64, remaining) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let buf = &mut buf[..len];
+        let n = match &mut self.source {
+            Source::Raw { reader, start } => reader.read_at(buf, *start + self.pos)?,
+            Source::Cached { cluster, start } => {
+                let from = *start + self.pos as usize;
+                buf.copy_from_slice(&cluster.data[from..from + len]);
+                len
+            }
+            Source::Zstd(decoder) => decoder.read(buf)?,
+        };
+        // Blob offsets were checked against the cluster, running out of data means it's corrupted.
+        if n == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: ReadAt> Seek for BlobReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::End(delta) => self.size.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        }
+        .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+
+        if let Source::Zstd(decoder) = &mut self.source {
+            if target < self.pos {
+                return Err(io::Error::new(io::ErrorKind::Unsupported, "can't seek backwards in a compressed blob"));
+            }
+            let skip = std::cmp::min(target, self.size) - std::cmp::min(self.pos, self.size);
+            if skip_bytes(decoder, skip)? < skip {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+        }
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+fn skip_bytes(reader: &mut impl Read, count: u64) -> io::Result<u64> {
+    io::copy(&mut reader.take(count), &mut io::sink())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+
+    /// A cluster holding `blobs`, as stored in an archive.
+    fn cluster_bytes(blobs: &[&[u8]], compression: Compression) -> Vec<u8> {
+        let table_size = 4 * (blobs.len() as u32 + 1);
+        let mut body = Vec::new();
+        let mut offset = table_size;
+        body.extend_from_slice(&offset.to_le_bytes());
+        for blob in blobs {
+            offset += blob.len() as u32;
+            body.extend_from_slice(&offset.to_le_bytes());
+        }
+        for blob in blobs {
+            body.extend_from_slice(blob);
+        }
+
+        let mut data = vec![compression as u8];
+        match compression {
+            Compression::Zstd => data.extend(compress_to_vec(&body[..], CompressionLevel::Fastest)),
+            _ => data.extend(body),
+        }
+        data
+    }
+
+    fn zstd_reader(data: &Vec<u8>, index: u32) -> Result<BlobReader<'_, Vec<u8>>, ZimError> {
+        let reader = BufReader::new(ReadAtCursor::new(data, 1).take(data.len() as u64 - 1));
+        BlobReader::zstd(reader, false, index)
+    }
+
+    #[test]
+    fn test_uncompressed_blob_read_and_seek() {
+        let data = cluster_bytes(&[b"first", b"second blob", b""], Compression::None);
+        let mut reader = BlobReader::uncompressed(&data, 0, data.len() as u64, false, 1).unwrap();
+        assert_eq!(reader.size(), 11);
+        assert!(reader.is_seekable());
+
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "second blob");
+
+        reader.seek(SeekFrom::End(-4)).unwrap();
+        content.clear();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "blob");
+
+        let mut empty = BlobReader::uncompressed(&data, 0, data.len() as u64, false, 2).unwrap();
+        assert_eq!(empty.read(&mut [0u8; 4]).unwrap(), 0);
+        assert!(matches!(
+            BlobReader::uncompressed(&data, 0, data.len() as u64, false, 3),
+            Err(ZimError::OutOfBounds { index: 3, count: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_uncompressed_blob_truncated() {
+        let data = cluster_bytes(&[b"first", b"second"], Compression::None);
+        assert!(matches!(
+            BlobReader::uncompressed(&data, 0, data.len() as u64 - 2, false, 1),
+            Err(ZimError::TruncatedCluster { .. })
+        ));
+    }
+
+    #[test]
+    fn test_zstd_blob_read_and_forward_seek() {
+        let large = vec![0x5a; 100_000];
+        let data = cluster_bytes(&[b"first", &large, b"last"], Compression::Zstd);
+
+        let mut reader = zstd_reader(&data, 1).unwrap();
+        assert!(!reader.is_seekable());
+        let mut out = Vec::new();
+        assert_eq!(io::copy(&mut reader, &mut out).unwrap(), large.len() as u64);
+        assert_eq!(out, large);
+
+        let mut reader = zstd_reader(&data, 2).unwrap();
+        reader.seek(SeekFrom::Start(2)).unwrap();
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "st");
+        assert!(reader.seek(SeekFrom::Start(0)).is_err());
+    }
+
+    #[test]
+    fn test_cached_blob() {
+        let data = cluster_bytes(&[b"first", b"second"], Compression::None);
+        let cluster = Arc::new(Cluster::parse(&data[..]).unwrap());
+        let mut reader = BlobReader::<Vec<u8>>::cached(cluster, 1).unwrap();
+
+        reader.seek(SeekFrom::Start(3)).unwrap();
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "ond");
+    }
+}
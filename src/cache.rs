@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::cluster::Cluster;
+
+/// Limits of the decompressed cluster cache of an `Archive`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CacheConfig {
+    /// Maximum number of clusters kept, 0 disables the cache.
+    pub max_clusters: usize,
+    /// Maximum total size of the cached (decompressed) cluster data.
+    pub max_bytes: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            max_clusters: 16,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Counters of the cluster cache.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub clusters: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    cluster: Arc<Cluster>,
+    size: u64,
+    last_used: u64,
+}
+
+/// Least recently used cache of parsed clusters keyed by cluster number.
+#[derive(Debug)]
+pub(crate) struct ClusterCache {
+    config: CacheConfig,
+    entries: HashMap<u32, CacheEntry>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl ClusterCache {
+    pub fn new(config: CacheConfig) -> Self {
+        ClusterCache {
+            config,
+            entries: HashMap::new(),
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn get(&mut self, cluster_number: u32) -> Option<Arc<Cluster>> {
+        self.tick += 1;
+        match self.entries.get_mut(&cluster_number) {
+            Some(entry) => {
+                entry.last_used = self.tick;
+                self.stats.hits += 1;
+                Some(Arc::clone(&entry.cluster))
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, cluster_number: u32, cluster: Arc<Cluster>) {
+        let size = cluster.data.len() as u64;
+        if self.config.max_clusters == 0 || size > self.config.max_bytes {
+            return;
+        }
+
+        self.tick += 1;
+        if let Some(old) = self.entries.insert(cluster_number, CacheEntry { cluster, size, last_used: self.tick }) {
+            self.stats.bytes -= old.size;
+        }
+        self.stats.bytes += size;
+
+        while self.entries.len() > self.config.max_clusters || self.stats.bytes > self.config.max_bytes {
+            self.evict_least_recently_used();
+        }
+        self.stats.clusters = self.entries.len();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        let oldest = self.entries.iter().min_by_key(|(_, e)| e.last_used).map(|(&k, _)| k);
+        if let Some(entry) = oldest.and_then(|k| self.entries.remove(&k)) {
+            self.stats.bytes -= entry.size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::Compression;
+
+    fn cluster(size: usize) -> Arc<Cluster> {
+        Arc::new(Cluster {
+            compression: Compression::None,
+            is_extended: false,
+            blob_offsets: vec![8, 8 + size as u64],
+            data: vec![0; size],
+        })
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_by_count() {
+        let mut cache = ClusterCache::new(CacheConfig { max_clusters: 2, max_bytes: 1000 });
+        cache.insert(0, cluster(10));
+        cache.insert(1, cluster(10));
+        assert!(cache.get(0).is_some());
+        cache.insert(2, cluster(10));
+
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(0).is_some());
+        assert!(cache.get(2).is_some());
+        assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 1, clusters: 2, bytes: 20 });
+    }
+
+    #[test]
+    fn test_evicts_by_total_bytes() {
+        let mut cache = ClusterCache::new(CacheConfig { max_clusters: 10, max_bytes: 25 });
+        cache.insert(0, cluster(10));
+        cache.insert(1, cluster(10));
+        cache.insert(2, cluster(10));
+        assert_eq!(cache.stats().clusters, 2);
+        assert_eq!(cache.stats().bytes, 20);
+        assert!(cache.get(0).is_none());
+
+        // Larger than the whole cache: not kept
+        cache.insert(3, cluster(30));
+        assert!(cache.get(3).is_none());
+    }
+
+    #[test]
+    fn test_disabled_cache() {
+        let mut cache = ClusterCache::new(CacheConfig { max_clusters: 0, max_bytes: 1000 });
+        cache.insert(0, cluster(10));
+        assert!(cache.get(0).is_none());
+        assert_eq!(cache.stats().misses, 1);
+    }
+}
@@ -1,7 +1,28 @@
-use std::io::Read;
+use std::fmt;
+use std::io::{BufReader, Cursor, Read, Write};
+use ruzstd::decoding::StreamingDecoder;
+use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+use crate::error::{Structure, ZimError};
 
 const MAX_BLOBS: u64 = 1_000_000;
 
+/// Zstd levels implemented by the pure Rust encoder, used when writing zstd clusters.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ZstdLevel {
+    /// A valid zstd frame made of raw blocks, without actual compression.
+    Uncompressed,
+    Fastest,
+}
+
+impl ZstdLevel {
+    fn encoder_level(self) -> CompressionLevel {
+        match self {
+            ZstdLevel::Uncompressed => CompressionLevel::Uncompressed,
+            ZstdLevel::Fastest => CompressionLevel::Fastest,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Compression {
     None = 1,
@@ -11,78 +32,45 @@ pub enum Compression {
     Zstd = 5,
 }
 
-#[derive(Debug)]
+/// A cluster of blobs, holding the data of one or more items.
+#[derive(PartialEq, Clone)]
 pub struct Cluster {
     pub compression: Compression,
     pub is_extended: bool,
     pub blob_offsets: Vec<u64>,
+    /// Uncompressed blob data following the offset table.
+    pub data: Vec<u8>,
 }
 
 impl Cluster {
-    pub fn parse(mut reader: impl Read) -> Result<Self, String> {
-        let mut byte = [0u8; 1];
-        reader.read_exact(&mut byte).map_err(|e| e.to_string())?;
-        
-        let compression_byte = byte[0];
-        let compression_val = compression_byte & 0x0F;
-        let is_extended = (compression_byte & 0x10) != 0;
-        
-        let compression = match compression_val {
-            1 => Compression::None,
-            2 => Compression::Zip,
-            3 => Compression::Bzip2,
-            4 => Compression::Lzma,
-            5 => Compression::Zstd,
-            _ => return Err(format!("Invalid compression type: {}", compression_val)),
-        };
+    pub fn parse(mut reader: impl Read) -> Result<Self, ZimError> {
+        let (compression, is_extended) = read_info(&mut reader)?;
 
-        let mut blob_offsets = Vec::new();
-
-        // TODO: Support decompression for Zstd and Lzma.
-        // For now we can only parse the offsets if there is no compression.
-        // If compressed, we would need to wrap the reader in a decompressor first
-        // because the offsets are at the beginning of the uncompressed data.
-        if compression == Compression::None {
-             if is_extended {
-                let mut buf = [0u8; 8];
-                reader.read_exact(&mut buf).map_err(|e| e.to_string())?;
-                let first_offset = u64::from_le_bytes(buf);
-                blob_offsets.push(first_offset);
-                
-                let count = first_offset / 8;
-                // Basic sanity check to prevent OOM on bad data
-                if count > MAX_BLOBS {
-                     return Err(format!("Too many blobs in cluster: {}", count));
-                }
-
-                for _ in 1..count {
-                    reader.read_exact(&mut buf).map_err(|e| e.to_string())?;
-                    let offset = u64::from_le_bytes(buf);
-                    blob_offsets.push(offset);
-                }
-            } else {
-                 let mut buf = [0u8; 4];
-                reader.read_exact(&mut buf).map_err(|e| e.to_string())?;
-                let first_offset = u32::from_le_bytes(buf) as u64;
-                blob_offsets.push(first_offset);
-                
-                let count = first_offset / 4;
-                if count > MAX_BLOBS {
-                     return Err(format!("Too many blobs in cluster: {}", count));
-                }
-
-                for _ in 1..count {
-                    reader.read_exact(&mut buf).map_err(|e| e.to_string())?;
-                    let offset = u32::from_le_bytes(buf) as u64;
-                    blob_offsets.push(offset);
-                }
+        let (blob_offsets, data) = match compression {
+            Compression::None => read_cluster_body(&mut reader, is_extended)?,
+            Compression::Zstd => {
+                // The offsets are at the beginning of the uncompressed data,
+                // so the reader has to be wrapped in a decompressor first.
+                let mut decoder = StreamingDecoder::new(&mut reader)
+                    .map_err(|e| decompression_error(compression, e))?;
+                read_cluster_body(&mut decoder, is_extended)?
             }
-        }
+            Compression::Lzma => {
+                // The xz decoder rejects trailing data, so the reader must be
+                // bounded to the end of this cluster.
+                let mut decompressed = Vec::new();
+                lzma_rs::xz_decompress(&mut BufReader::new(&mut reader), &mut decompressed)
+                    .map_err(|e| decompression_error(compression, e))?;
+                read_cluster_body(&mut Cursor::new(decompressed), is_extended)?
+            }
+            Compression::Zip | Compression::Bzip2 => return Err(ZimError::UnsupportedCompression { compression }),
+        };
 
         Ok(Cluster {
             compression,
             is_extended,
             blob_offsets,
+            data,
         })
     }
 
@@ -100,12 +88,182 @@ impl Cluster {
         }
         Some(self.blob_offsets[index + 1] - self.blob_offsets[index])
     }
+
+    pub fn get_blob(&self, index: usize) -> Option<&[u8]> {
+        if index + 1 >= self.blob_offsets.len() {
+            return None;
+        }
+        // Offsets are relative to the start of the offset table, data starts right after it.
+        let table_size = self.blob_offsets[0];
+        let start = (self.blob_offsets[index] - table_size) as usize;
+        let end = (self.blob_offsets[index + 1] - table_size) as usize;
+        self.data.get(start..end)
+    }
+
+    /// Cluster holding `blobs`, extended (64-bit offsets) only when 32 bits can't hold them.
+    pub fn from_blobs(compression: Compression, blobs: &[impl AsRef<[u8]>]) -> Self {
+        let data_size: u64 = blobs.iter().map(|b| b.as_ref().len() as u64).sum();
+        let is_extended = 4 * (blobs.len() as u64 + 1) + data_size > u32::MAX as u64;
+        let offset_size = if is_extended { 8 } else { 4 };
+
+        let mut blob_offsets = Vec::with_capacity(blobs.len() + 1);
+        let mut data = Vec::with_capacity(data_size as usize);
+        blob_offsets.push(offset_size * (blobs.len() as u64 + 1));
+        for blob in blobs {
+            data.extend_from_slice(blob.as_ref());
+            blob_offsets.push(blob_offsets[0] + data.len() as u64);
+        }
+        Cluster { compression, is_extended, blob_offsets, data }
+    }
+
+    /// Inverse of `parse`: the info byte, then the offset table and data, compressed as a whole.
+    /// `zstd_level` is only used by zstd clusters. Zip and Bzip2 can't be written, and offsets
+    /// above 32 bits need an extended cluster.
+    pub fn write_to(&self, mut writer: impl Write, zstd_level: ZstdLevel) -> Result<(), ZimError> {
+        writer.write_all(&self.to_bytes(zstd_level)?).map_err(|e| ZimError::io(Structure::Cluster, e))
+    }
+
+    pub fn to_bytes(&self, zstd_level: ZstdLevel) -> Result<Vec<u8>, ZimError> {
+        let offset_size = if self.is_extended { 8 } else { 4 };
+        let mut body = Vec::with_capacity(offset_size * self.blob_offsets.len() + self.data.len());
+        for &offset in &self.blob_offsets {
+            if self.is_extended {
+                body.extend_from_slice(&offset.to_le_bytes());
+            } else {
+                let offset = u32::try_from(offset)
+                    .map_err(|_| ZimError::InvalidField { structure: Structure::Cluster, field: "blob offset" })?;
+                body.extend_from_slice(&offset.to_le_bytes());
+            }
+        }
+        body.extend_from_slice(&self.data);
+
+        let mut bytes = vec![self.compression as u8 | if self.is_extended { 0x10 } else { 0 }];
+        match self.compression {
+            Compression::None => bytes.extend(body),
+            Compression::Zstd => bytes.extend(compress_to_vec(&body[..], zstd_level.encoder_level())),
+            Compression::Lzma => lzma_rs::xz_compress(&mut &body[..], &mut bytes)
+                .map_err(|e| ZimError::io(Structure::Cluster, e))?,
+            Compression::Zip | Compression::Bzip2 => {
+                return Err(ZimError::UnsupportedCompression { compression: self.compression });
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+/// Shows the size of the data rather than the bytes, which can be megabytes per cluster.
+impl fmt::Debug for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cluster")
+            .field("compression", &self.compression)
+            .field("is_extended", &self.is_extended)
+            .field("blob_offsets", &self.blob_offsets)
+            .field("data_len", &self.data.len())
+            .finish()
+    }
+}
+
+/// Reads the info byte starting a cluster: its compression and whether offsets are 64 bits.
+pub(crate) fn read_info(reader: &mut impl Read) -> Result<(Compression, bool), ZimError> {
+    let mut byte = [0u8; 1];
+    reader.read_exact(&mut byte).map_err(|e| ZimError::io(Structure::Cluster, e))?;
+
+    let compression_byte = byte[0];
+    let compression_val = compression_byte & 0x0F;
+    let is_extended = (compression_byte & 0x10) != 0;
+
+    let compression = match compression_val {
+        1 => Compression::None,
+        2 => Compression::Zip,
+        3 => Compression::Bzip2,
+        4 => Compression::Lzma,
+        5 => Compression::Zstd,
+        _ => return Err(ZimError::InvalidCompression { offset: 0, value: compression_val }),
+    };
+    Ok((compression, is_extended))
+}
+
+/// Reads the offset table of an (uncompressed) cluster body up to blob `index`,
+/// returning the blob start and end relative to the start of the table.
+///
+/// The reader is left right after the end offset of the blob.
+pub(crate) fn read_blob_range(reader: &mut impl Read, is_extended: bool, index: u32) -> Result<(u64, u64), ZimError> {
+    let offset_size = if is_extended { 8 } else { 4 };
+    let first_offset = read_offset(reader, is_extended)?;
+    let count = (first_offset / offset_size).saturating_sub(1);
+    if index as u64 >= count {
+        return Err(ZimError::OutOfBounds { structure: Structure::Cluster, index: index as u64, count });
+    }
+
+    let mut start = first_offset;
+    for _ in 0..index {
+        start = read_offset(reader, is_extended)?;
+    }
+    let end = read_offset(reader, is_extended)?;
+    if start < first_offset || end < start {
+        return Err(ZimError::InvalidBlobOffsets { offset: 0 });
+    }
+    Ok((start, end))
+}
+
+pub(crate) fn decompression_error(compression: Compression, e: impl std::fmt::Display) -> ZimError {
+    ZimError::Decompression { offset: 0, compression, message: e.to_string() }
+}
+
+fn read_cluster_body(reader: &mut impl Read, is_extended: bool) -> Result<(Vec<u64>, Vec<u8>), ZimError> {
+    let blob_offsets = read_blob_offsets(reader, is_extended)?;
+    let data_size = match (blob_offsets.first(), blob_offsets.last()) {
+        (Some(&first), Some(&last)) => last - first,
+        _ => 0,
+    };
+
+    // Read through `take` so a corrupted size fails on EOF instead of allocating it upfront.
+    let mut data = Vec::new();
+    reader.take(data_size).read_to_end(&mut data).map_err(|e| ZimError::io(Structure::Cluster, e))?;
+    if (data.len() as u64) < data_size {
+        return Err(ZimError::TruncatedCluster { offset: 0, expected: data_size, found: data.len() as u64 });
+    }
+    Ok((blob_offsets, data))
+}
+
+fn read_blob_offsets(reader: &mut impl Read, is_extended: bool) -> Result<Vec<u64>, ZimError> {
+    let offset_size = if is_extended { 8 } else { 4 };
+    let first_offset = read_offset(reader, is_extended)?;
+
+    let count = first_offset / offset_size;
+    // Basic sanity check to prevent OOM on bad data
+    if count > MAX_BLOBS {
+        return Err(ZimError::TooManyBlobs { offset: 0, count });
+    }
+
+    let mut blob_offsets = Vec::with_capacity(count as usize);
+    blob_offsets.push(first_offset);
+    for _ in 1..count {
+        let offset = read_offset(reader, is_extended)?;
+        if offset < blob_offsets[blob_offsets.len() - 1] {
+            return Err(ZimError::InvalidBlobOffsets { offset: 0 });
+        }
+        blob_offsets.push(offset);
+    }
+    Ok(blob_offsets)
+}
+
+fn read_offset(reader: &mut impl Read, is_extended: bool) -> Result<u64, ZimError> {
+    if is_extended {
+        let mut buf = [0u8; 8];
+        reader.read_exact(&mut buf).map_err(|e| ZimError::io(Structure::Cluster, e))?;
+        Ok(u64::from_le_bytes(buf))
+    } else {
+        let mut buf = [0u8; 4];
+        reader.read_exact(&mut buf).map_err(|e| ZimError::io(Structure::Cluster, e))?;
+        Ok(u32::from_le_bytes(buf) as u64)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
+    use crate::test_util::Rng;
 
     #[test]
     fn test_parse_uncompressed_cluster_32bit() {
@@ -129,8 +287,8 @@ mod tests {
         data.extend_from_slice(&off2.to_le_bytes());
         
         // Blob data
-        data.extend(std::iter::repeat(0xAA).take(10)); // Blob 0
-        data.extend(std::iter::repeat(0xBB).take(5));  // Blob 1
+        data.extend(std::iter::repeat_n(0xAA, 10)); // Blob 0
+        data.extend(std::iter::repeat_n(0xBB, 5));  // Blob 1
         
         let mut reader = Cursor::new(data);
         let cluster = Cluster::parse(&mut reader).expect("Failed to parse cluster");
@@ -145,17 +303,244 @@ mod tests {
         assert_eq!(cluster.count(), 2);
         assert_eq!(cluster.get_blob_size(0), Some(10));
         assert_eq!(cluster.get_blob_size(1), Some(5));
+        assert_eq!(cluster.get_blob(0), Some(&[0xAA; 10][..]));
+        assert_eq!(cluster.get_blob(1), Some(&[0xBB; 5][..]));
+        assert_eq!(cluster.get_blob(2), None);
     }
 
     #[test]
-    fn test_parse_compressed_cluster_info() {
-        // Just test that we correctly identify compression type even if we don't parse offsets
-        let data = vec![0x15]; // Zstd (5) | Extended (0x10)
-        let mut reader = Cursor::new(data);
+    fn test_read_blob_range() {
+        let mut body = Vec::new();
+        for offset in [12u32, 16, 22] {
+            body.extend_from_slice(&offset.to_le_bytes());
+        }
+        assert_eq!(read_blob_range(&mut Cursor::new(&body), false, 0).unwrap(), (12, 16));
+        assert_eq!(read_blob_range(&mut Cursor::new(&body), false, 1).unwrap(), (16, 22));
+        assert!(matches!(
+            read_blob_range(&mut Cursor::new(&body), false, 2),
+            Err(ZimError::OutOfBounds { index: 2, count: 2, .. })
+        ));
+
+        let mut extended = Vec::new();
+        for offset in [16u64, 12] {
+            extended.extend_from_slice(&offset.to_le_bytes());
+        }
+        assert!(matches!(read_blob_range(&mut Cursor::new(&extended), true, 0), Err(ZimError::InvalidBlobOffsets { .. })));
+    }
+
+    fn zstd_cluster(compression_byte: u8, body: &[u8]) -> Vec<u8> {
+        let mut data = vec![compression_byte];
+        data.extend(compress_to_vec(body, CompressionLevel::Fastest));
+        data
+    }
+
+    #[test]
+    fn test_parse_zstd_cluster_32bit() {
+        // Uncompressed body: 3 offsets (12 bytes) followed by 2 blobs of 4 and 6 bytes
+        let mut body = Vec::new();
+        body.extend_from_slice(&12u32.to_le_bytes());
+        body.extend_from_slice(&16u32.to_le_bytes());
+        body.extend_from_slice(&22u32.to_le_bytes());
+        body.extend_from_slice(b"abcd");
+        body.extend_from_slice(b"efghij");
+
+        let mut reader = Cursor::new(zstd_cluster(0x05, &body));
         let cluster = Cluster::parse(&mut reader).expect("Failed to parse cluster");
-        
+
+        assert_eq!(cluster.compression, Compression::Zstd);
+        assert!(!cluster.is_extended);
+        assert_eq!(cluster.blob_offsets, vec![12, 16, 22]);
+        assert_eq!(cluster.count(), 2);
+        assert_eq!(cluster.get_blob_size(0), Some(4));
+        assert_eq!(cluster.get_blob_size(1), Some(6));
+        assert_eq!(cluster.get_blob(0), Some(&b"abcd"[..]));
+        assert_eq!(cluster.get_blob(1), Some(&b"efghij"[..]));
+    }
+
+    #[test]
+    fn test_parse_zstd_cluster_extended() {
+        // Zstd (5) | Extended (0x10) -> 0x15, offsets are 64-bit
+        let mut body = Vec::new();
+        body.extend_from_slice(&16u64.to_le_bytes());
+        body.extend_from_slice(&21u64.to_le_bytes());
+        body.extend_from_slice(b"hello");
+
+        let mut reader = Cursor::new(zstd_cluster(0x15, &body));
+        let cluster = Cluster::parse(&mut reader).expect("Failed to parse cluster");
+
         assert_eq!(cluster.compression, Compression::Zstd);
         assert!(cluster.is_extended);
-        assert!(cluster.blob_offsets.is_empty());
+        assert_eq!(cluster.blob_offsets, vec![16, 21]);
+        assert_eq!(cluster.get_blob_size(0), Some(5));
+        assert_eq!(cluster.get_blob(0), Some(&b"hello"[..]));
+    }
+
+    #[test]
+    fn test_parse_zstd_cluster_invalid_frame() {
+        let data = vec![0x05, 0x00, 0x01, 0x02, 0x03];
+        let mut reader = Cursor::new(data);
+        assert!(Cluster::parse(&mut reader).is_err());
+    }
+
+    fn xz_cluster(compression_byte: u8, body: &[u8]) -> Vec<u8> {
+        let mut data = vec![compression_byte];
+        lzma_rs::xz_compress(&mut &body[..], &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_parse_lzma_cluster_32bit() {
+        // Lzma (4) | Not extended -> 0x04
+        let mut body = Vec::new();
+        body.extend_from_slice(&12u32.to_le_bytes());
+        body.extend_from_slice(&15u32.to_le_bytes());
+        body.extend_from_slice(&20u32.to_le_bytes());
+        body.extend_from_slice(b"foo");
+        body.extend_from_slice(b"barba");
+
+        let mut reader = Cursor::new(xz_cluster(0x04, &body));
+        let cluster = Cluster::parse(&mut reader).expect("Failed to parse cluster");
+
+        assert_eq!(cluster.compression, Compression::Lzma);
+        assert!(!cluster.is_extended);
+        assert_eq!(cluster.blob_offsets, vec![12, 15, 20]);
+        assert_eq!(cluster.get_blob_size(0), Some(3));
+        assert_eq!(cluster.get_blob_size(1), Some(5));
+        assert_eq!(cluster.get_blob(0), Some(&b"foo"[..]));
+        assert_eq!(cluster.get_blob(1), Some(&b"barba"[..]));
+    }
+
+    #[test]
+    fn test_parse_lzma_cluster_extended_bounded() {
+        // Lzma (4) | Extended (0x10) -> 0x14, followed by unrelated trailing bytes
+        let mut body = Vec::new();
+        body.extend_from_slice(&16u64.to_le_bytes());
+        body.extend_from_slice(&18u64.to_le_bytes());
+        body.extend_from_slice(b"ok");
+
+        let cluster_data = xz_cluster(0x14, &body);
+        let cluster_size = cluster_data.len() as u64;
+        let mut data = cluster_data;
+        data.extend_from_slice(&[0x01, 0x02, 0x03]);
+
+        let mut reader = Cursor::new(data);
+        let cluster = Cluster::parse((&mut reader).take(cluster_size)).expect("Failed to parse cluster");
+
+        assert_eq!(cluster.compression, Compression::Lzma);
+        assert!(cluster.is_extended);
+        assert_eq!(cluster.blob_offsets, vec![16, 18]);
+        assert_eq!(cluster.get_blob(0), Some(&b"ok"[..]));
+    }
+
+    #[test]
+    fn test_parse_lzma_cluster_invalid_stream() {
+        let data = vec![0x04, 0xFD, 0x37, 0x7A, 0x00];
+        let mut reader = Cursor::new(data);
+        assert!(Cluster::parse(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_parse_unsupported_compression() {
+        for (info, compression) in [(0x02, Compression::Zip), (0x13, Compression::Bzip2)] {
+            let data = vec![info, 0x08, 0, 0, 0, 0x08, 0, 0, 0];
+            let result = Cluster::parse(&data[..]);
+            assert!(matches!(result, Err(ZimError::UnsupportedCompression { compression: c }) if c == compression));
+        }
+    }
+
+    #[test]
+    fn test_parse_truncated_cluster_data() {
+        // Offsets announce 8 bytes of blob data but only 3 are present
+        let mut data = vec![0x01];
+        data.extend_from_slice(&8u32.to_le_bytes());
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(b"abc");
+
+        let mut reader = Cursor::new(data);
+        assert!(Cluster::parse(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_debug_omits_data() {
+        let cluster = Cluster::from_blobs(Compression::None, &[vec![b'x'; 1000]]);
+        assert_eq!(
+            format!("{:?}", cluster),
+            "Cluster { compression: None, is_extended: false, blob_offsets: [8, 1008], data_len: 1000 }"
+        );
+    }
+
+    #[test]
+    fn test_from_blobs() {
+        let cluster = Cluster::from_blobs(Compression::None, &[&b"ab"[..], b"", b"cde"]);
+        assert!(!cluster.is_extended);
+        assert_eq!(cluster.blob_offsets, vec![16, 18, 18, 21]);
+        assert_eq!(cluster.get_blob(2), Some(&b"cde"[..]));
+        assert_eq!(cluster.to_bytes(ZstdLevel::Fastest).unwrap()[0], 0x01);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = Rng::new(0xc105);
+        let compressions = [Compression::None, Compression::Zstd, Compression::Lzma];
+        for i in 0..60 {
+            let blob_count = rng.below(5) as usize;
+            let blobs: Vec<Vec<u8>> = (0..blob_count)
+                .map(|_| {
+                    let len = rng.below(300) as usize;
+                    // Runs of a few values, so the compressors have something to work with
+                    (0..len).map(|j| (j / (1 + rng.below(8) as usize)) as u8).collect()
+                })
+                .collect();
+            let mut cluster = Cluster::from_blobs(compressions[i % 3], &blobs);
+            if rng.below(2) == 0 {
+                // Extended offset table: same data, 8 bytes per offset
+                let shift = 4 * cluster.blob_offsets.len() as u64;
+                cluster.blob_offsets.iter_mut().for_each(|offset| *offset += shift);
+                cluster.is_extended = true;
+            }
+
+            let level = if rng.below(2) == 0 { ZstdLevel::Uncompressed } else { ZstdLevel::Fastest };
+            let bytes = cluster.to_bytes(level).unwrap();
+            let parsed = Cluster::parse(&bytes[..]).unwrap();
+            assert_eq!(parsed, cluster);
+            for (index, blob) in blobs.iter().enumerate() {
+                assert_eq!(parsed.get_blob(index), Some(&blob[..]));
+            }
+
+            let mut written = Vec::new();
+            cluster.write_to(&mut written, level).unwrap();
+            assert_eq!(written, bytes);
+        }
+    }
+
+    #[test]
+    fn test_write_unsupported_compression() {
+        let cluster = Cluster::from_blobs(Compression::Bzip2, &[b"x"]);
+        assert!(matches!(cluster.to_bytes(ZstdLevel::Fastest), Err(ZimError::UnsupportedCompression { compression: Compression::Bzip2 })));
+    }
+
+    #[test]
+    fn test_write_zstd_levels() {
+        let cluster = Cluster::from_blobs(Compression::Zstd, &[vec![b'a'; 4000], vec![b'b'; 4000]]);
+        let uncompressed = cluster.to_bytes(ZstdLevel::Uncompressed).unwrap();
+        let fastest = cluster.to_bytes(ZstdLevel::Fastest).unwrap();
+        assert!(uncompressed.len() > 8000);
+        assert!(fastest.len() < 1000);
+        assert_eq!(Cluster::parse(&uncompressed[..]).unwrap(), cluster);
+        assert_eq!(Cluster::parse(&fastest[..]).unwrap(), cluster);
+    }
+
+    #[test]
+    fn test_write_offset_overflow() {
+        // Offsets only fit in an extended cluster
+        let cluster = Cluster {
+            compression: Compression::None,
+            is_extended: false,
+            blob_offsets: vec![8, u32::MAX as u64 + 1],
+            data: Vec::new(),
+        };
+        assert!(matches!(cluster.to_bytes(ZstdLevel::Fastest), Err(ZimError::InvalidField { structure: Structure::Cluster, .. })));
+        let extended = Cluster { is_extended: true, ..cluster };
+        assert_eq!(extended.to_bytes(ZstdLevel::Fastest).unwrap().len(), 17);
     }
 }
@@ -1,10 +1,13 @@
-use std::io::Read;
+use std::cmp::Ordering;
+use std::io::{Read, Write};
+use crate::error::{Structure, ZimError};
 
 pub const REDIRECT_MIME_TYPE: u16 = 0xffff;
 pub const LINK_TARGET_MIME_TYPE: u16 = 0xfffe;
 pub const DELETED_MIME_TYPE: u16 = 0xfffd;
 
-#[derive(Debug)]
+/// What a dirent points to, depending on its mime type.
+#[derive(Debug, PartialEq, Clone)]
 pub enum DirentData {
     Content {
         cluster_number: u32,
@@ -17,7 +20,8 @@ pub enum DirentData {
     Deleted,
 }
 
-#[derive(Debug)]
+/// Directory entry: the metadata of one entry of the archive (path, title, mime type...).
+#[derive(Debug, PartialEq, Clone)]
 pub struct Dirent {
     pub mime_type: u16,
     pub extra_len: u8,
@@ -30,9 +34,9 @@ pub struct Dirent {
 }
 
 impl Dirent {
-    pub fn parse(mut reader: impl Read) -> Result<Self, String> {
+    pub fn parse(mut reader: impl Read) -> Result<Self, ZimError> {
         let mut fixed_buf = [0u8; 8];
-        reader.read_exact(&mut fixed_buf).map_err(|e| e.to_string())?;
+        reader.read_exact(&mut fixed_buf).map_err(|e| ZimError::io(Structure::Dirent, e))?;
 
         let mime_type = u16::from_le_bytes(fixed_buf[0..2].try_into().unwrap());
         let extra_len = fixed_buf[2];
@@ -42,7 +46,7 @@ impl Dirent {
         let data = match mime_type {
             REDIRECT_MIME_TYPE => {
                 let mut buf = [0u8; 4];
-                reader.read_exact(&mut buf).map_err(|e| e.to_string())?;
+                reader.read_exact(&mut buf).map_err(|e| ZimError::io(Structure::Dirent, e))?;
                 DirentData::Redirect {
                     redirect_index: u32::from_le_bytes(buf),
                 }
@@ -51,7 +55,7 @@ impl Dirent {
             DELETED_MIME_TYPE => DirentData::Deleted,
             _ => {
                 let mut buf = [0u8; 8];
-                reader.read_exact(&mut buf).map_err(|e| e.to_string())?;
+                reader.read_exact(&mut buf).map_err(|e| ZimError::io(Structure::Dirent, e))?;
                 DirentData::Content {
                     cluster_number: u32::from_le_bytes(buf[0..4].try_into().unwrap()),
                     blob_number: u32::from_le_bytes(buf[4..8].try_into().unwrap()),
@@ -64,7 +68,7 @@ impl Dirent {
 
         let mut parameter = vec![0u8; extra_len as usize];
         if extra_len > 0 {
-            reader.read_exact(&mut parameter).map_err(|e| e.to_string())?;
+            reader.read_exact(&mut parameter).map_err(|e| ZimError::io(Structure::Dirent, e))?;
         }
 
         Ok(Dirent {
@@ -79,6 +83,60 @@ impl Dirent {
         })
     }
 
+    /// Inverse of `parse`.
+    ///
+    /// Fails with `InvalidField` for values that wouldn't read back the same: a namespace
+    /// above U+00FF, a parameter longer than 255 bytes or not matching `extra_len`, a NUL
+    /// in the url or title, or `data` not matching the mime type.
+    pub fn write_to(&self, mut writer: impl Write) -> Result<(), ZimError> {
+        writer.write_all(&self.to_bytes()?).map_err(|e| ZimError::io(Structure::Dirent, e))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ZimError> {
+        let invalid = |field| ZimError::InvalidField { structure: Structure::Dirent, field };
+        // `parse` reads the namespace as a single byte, mapping it to U+0000..U+00FF.
+        let namespace = u8::try_from(self.namespace).map_err(|_| invalid("namespace"))?;
+        let extra_len = u8::try_from(self.parameter.len()).map_err(|_| invalid("parameter"))?;
+        if extra_len != self.extra_len {
+            return Err(invalid("extra_len"));
+        }
+        if self.url.contains('\0') {
+            return Err(invalid("url"));
+        }
+        if self.title.contains('\0') {
+            return Err(invalid("title"));
+        }
+        let data_matches = match self.data {
+            DirentData::Redirect { .. } => self.mime_type == REDIRECT_MIME_TYPE,
+            DirentData::LinkTarget => self.mime_type == LINK_TARGET_MIME_TYPE,
+            DirentData::Deleted => self.mime_type == DELETED_MIME_TYPE,
+            DirentData::Content { .. } => self.is_article(),
+        };
+        if !data_matches {
+            return Err(invalid("data"));
+        }
+
+        let mut buffer = Vec::with_capacity(16 + self.url.len() + self.title.len() + self.parameter.len());
+        buffer.extend_from_slice(&self.mime_type.to_le_bytes());
+        buffer.push(extra_len);
+        buffer.push(namespace);
+        buffer.extend_from_slice(&self.revision.to_le_bytes());
+        match self.data {
+            DirentData::Content { cluster_number, blob_number } => {
+                buffer.extend_from_slice(&cluster_number.to_le_bytes());
+                buffer.extend_from_slice(&blob_number.to_le_bytes());
+            }
+            DirentData::Redirect { redirect_index } => buffer.extend_from_slice(&redirect_index.to_le_bytes()),
+            DirentData::LinkTarget | DirentData::Deleted => {}
+        }
+        buffer.extend_from_slice(self.url.as_bytes());
+        buffer.push(0);
+        buffer.extend_from_slice(self.title.as_bytes());
+        buffer.push(0);
+        buffer.extend_from_slice(&self.parameter);
+        Ok(buffer)
+    }
+
     pub fn is_redirect(&self) -> bool {
         self.mime_type == REDIRECT_MIME_TYPE
     }
@@ -95,6 +153,16 @@ impl Dirent {
         !self.is_redirect() && !self.is_link_target() && !self.is_deleted()
     }
 
+    /// Orders the dirent relative to a (namespace, path) pair, the order of the path pointer list.
+    pub fn cmp_path(&self, namespace: char, path: &str) -> Ordering {
+        (self.namespace, self.url.as_str()).cmp(&(namespace, path))
+    }
+
+    /// Orders the dirent relative to a (namespace, title) pair, the order of the title index.
+    pub fn cmp_title(&self, namespace: char, title: &str) -> Ordering {
+        (self.namespace, self.get_title()).cmp(&(namespace, title))
+    }
+
     pub fn get_title(&self) -> &str {
         if self.title.is_empty() {
             &self.url
@@ -104,7 +172,7 @@ impl Dirent {
     }
 }
 
-fn read_null_terminated_string(reader: &mut impl Read) -> Result<String, String> {
+fn read_null_terminated_string(reader: &mut impl Read) -> Result<String, ZimError> {
     let mut bytes = Vec::new();
     let mut buf = [0u8; 1];
     loop {
@@ -115,10 +183,10 @@ fn read_null_terminated_string(reader: &mut impl Read) -> Result<String, String>
                 }
                 bytes.push(buf[0]);
             }
-            Err(e) => return Err(e.to_string()),
+            Err(e) => return Err(ZimError::io(Structure::Dirent, e)),
         }
     }
-    String::from_utf8(bytes).map_err(|e| e.to_string())
+    String::from_utf8(bytes).map_err(|_| ZimError::InvalidUtf8 { structure: Structure::Dirent, offset: 0 })
 }
 
 #[cfg(test)]
@@ -134,6 +202,19 @@ mod tests {
         assert_eq!(read_null_terminated_string(&mut reader).unwrap(), "world");
     }
 
+    #[test]
+    fn test_parse_dirent_invalid_utf8() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&LINK_TARGET_MIME_TYPE.to_le_bytes());
+        data.push(0);
+        data.push(b'C');
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(b"\xff\xfe\0\0");
+
+        let result = Dirent::parse(Cursor::new(data));
+        assert!(matches!(result, Err(ZimError::InvalidUtf8 { structure: Structure::Dirent, .. })));
+    }
+
     #[test]
     fn test_parse_content_entry_dirent() {
         let mut data = Vec::new();
@@ -261,4 +342,72 @@ mod tests {
         assert!(dirent.is_deleted());
         assert!(matches!(dirent.data, DirentData::Deleted));
     }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = crate::test_util::Rng::new(0xd1e7);
+        for _ in 0..500 {
+            let (mime_type, data) = match rng.below(4) {
+                0 => (REDIRECT_MIME_TYPE, DirentData::Redirect { redirect_index: rng.next_u64() as u32 }),
+                1 => (LINK_TARGET_MIME_TYPE, DirentData::LinkTarget),
+                2 => (DELETED_MIME_TYPE, DirentData::Deleted),
+                _ => (rng.below(DELETED_MIME_TYPE as u64) as u16, DirentData::Content {
+                    cluster_number: rng.next_u64() as u32,
+                    blob_number: rng.next_u64() as u32,
+                }),
+            };
+            let parameter_len = rng.below(4) as usize;
+            let parameter = rng.bytes(parameter_len);
+            let dirent = Dirent {
+                mime_type,
+                extra_len: parameter.len() as u8,
+                namespace: (b' ' + rng.below(95) as u8) as char,
+                revision: rng.next_u64() as u32,
+                data,
+                url: rng.string(20),
+                title: rng.string(20),
+                parameter,
+            };
+
+            let bytes = dirent.to_bytes().unwrap();
+            let mut reader = Cursor::new(&bytes);
+            assert_eq!(Dirent::parse(&mut reader).unwrap(), dirent);
+            assert_eq!(reader.position(), bytes.len() as u64);
+
+            let mut written = Vec::new();
+            dirent.write_to(&mut written).unwrap();
+            assert_eq!(written, bytes);
+        }
+    }
+
+    #[test]
+    fn test_write_invalid_fields() {
+        let dirent = Dirent {
+            mime_type: 0,
+            extra_len: 0,
+            namespace: 'C',
+            revision: 0,
+            data: DirentData::Content { cluster_number: 0, blob_number: 0 },
+            url: "a".to_string(),
+            title: String::new(),
+            parameter: Vec::new(),
+        };
+        let field = |dirent: Dirent| match dirent.to_bytes() {
+            Err(ZimError::InvalidField { structure: Structure::Dirent, field }) => field,
+            other => panic!("Expected an invalid field, got {:?}", other),
+        };
+
+        // Latin-1 namespaces read back as the same char
+        let latin1 = Dirent { namespace: 'é', ..dirent.clone() };
+        assert_eq!(Dirent::parse(&latin1.to_bytes().unwrap()[..]).unwrap(), latin1);
+
+        assert_eq!(field(Dirent { namespace: '字', ..dirent.clone() }), "namespace");
+        assert_eq!(field(Dirent { extra_len: 255, parameter: vec![0; 256], ..dirent.clone() }), "parameter");
+        assert_eq!(field(Dirent { extra_len: 2, parameter: vec![0; 3], ..dirent.clone() }), "extra_len");
+        assert_eq!(field(Dirent { url: "a\0b".to_string(), ..dirent.clone() }), "url");
+        assert_eq!(field(Dirent { title: "\0".to_string(), ..dirent.clone() }), "title");
+        assert_eq!(field(Dirent { mime_type: REDIRECT_MIME_TYPE, ..dirent.clone() }), "data");
+        assert_eq!(field(Dirent { data: DirentData::Deleted, ..dirent.clone() }), "data");
+        assert!(Dirent { extra_len: 255, parameter: vec![0; 255], ..dirent }.to_bytes().is_ok());
+    }
 }
@@ -0,0 +1,189 @@
+use std::ops::Range;
+use crate::archive::Archive;
+use crate::dirent::Dirent;
+use crate::error::ZimError;
+use crate::namespace::NamespaceScheme;
+use crate::read_at::ReadAt;
+use crate::search::binary_search_by;
+
+/// Order in which `Entries` walks the archive.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EntryOrder {
+    /// Namespace and path, following the path pointer list.
+    Path,
+    /// Namespace and title, following the title index.
+    Title,
+}
+
+/// Lazy iterator over the entries of an `Archive`, reading each dirent on demand.
+///
+/// Yields the dirent index (its position in path order) with the dirent.
+/// Created by `Archive::entries_by_path` and `Archive::entries_by_title`, and narrowed
+/// with `namespace`, `content_only` and `non_redirects`.
+#[derive(Debug)]
+pub struct Entries<'a, R: ReadAt> {
+    archive: &'a Archive<R>,
+    order: EntryOrder,
+    positions: Range<u32>,
+    content_scheme: Option<NamespaceScheme>,
+    skip_redirects: bool,
+}
+
+impl<'a, R: ReadAt> Entries<'a, R> {
+    pub(crate) fn new(archive: &'a Archive<R>, order: EntryOrder) -> Self {
+        Entries {
+            archive,
+            order,
+            positions: 0..archive.entry_count(),
+            content_scheme: None,
+            skip_redirects: false,
+        }
+    }
+
+    pub fn order(&self) -> EntryOrder {
+        self.order
+    }
+
+    /// Only yields entries of `namespace`.
+    ///
+    /// Both orders sort by namespace first, so this narrows the range with two binary
+    /// searches instead of reading every dirent.
+    pub fn namespace(mut self, namespace: char) -> Result<Self, ZimError> {
+        let start = self.lower_bound(namespace)?;
+        let end = match char::from_u32(namespace as u32 + 1) {
+            Some(next) => self.lower_bound(next)?,
+            None => self.archive.entry_count(),
+        };
+        let start = start.max(self.positions.start);
+        self.positions = start..end.min(self.positions.end).max(start);
+        Ok(self)
+    }
+
+    /// Only yields entries of the content namespaces, see `NamespaceScheme`.
+    pub fn content_only(mut self) -> Self {
+        self.content_scheme = Some(self.archive.header.namespace_scheme());
+        self
+    }
+
+    /// Skips redirects.
+    pub fn non_redirects(mut self) -> Self {
+        self.skip_redirects = true;
+        self
+    }
+
+    /// First position in this order whose namespace is not before `namespace`.
+    fn lower_bound(&self, namespace: char) -> Result<u32, ZimError> {
+        let result = binary_search_by(self.archive.entry_count(), |position| {
+            let (_, dirent) = self.read(position)?;
+            Ok(match self.order {
+                EntryOrder::Path => dirent.cmp_path(namespace, ""),
+                EntryOrder::Title => dirent.cmp_title(namespace, ""),
+            })
+        })?;
+        Ok(result.unwrap_or_else(|insert_at| insert_at))
+    }
+
+    fn read(&self, position: u32) -> Result<(u32, Dirent), ZimError> {
+        let index = match self.order {
+            EntryOrder::Path => position,
+            EntryOrder::Title => self.archive.get_title_index_entry(position)?,
+        };
+        Ok((index, self.archive.get_dirent(index)?))
+    }
+
+    fn skips(&self, dirent: &Dirent) -> bool {
+        let not_content = self.content_scheme.is_some_and(|scheme| !scheme.is_content_namespace(dirent.namespace));
+        not_content || (self.skip_redirects && dirent.is_redirect())
+    }
+}
+
+impl<R: ReadAt> Iterator for Entries<'_, R> {
+    type Item = Result<(u32, Dirent), ZimError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(position) = self.positions.next() {
+            match self.read(position) {
+                Ok((_, dirent)) if self.skips(&dirent) => continue,
+                entry => return Some(entry),
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.positions.len();
+        let filtered = self.content_scheme.is_some() || self.skip_redirects;
+        (if filtered { 0 } else { remaining }, Some(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::test_util::{blob, build_zim, redirect, set_version};
+
+    fn test_archive() -> Archive<Cursor<Vec<u8>>> {
+        let data = build_zim(&[
+            blob('C', "a", "Zulu", b"0"),
+            blob('C', "b", "Alpha", b"1"),
+            redirect('C', "c", "Mike", 1),
+            blob('M', "Title", "", b"3"),
+            blob('W', "mainPage", "", b""),
+        ], 0);
+        Archive::new(Cursor::new(data)).unwrap()
+    }
+
+    fn urls<R: ReadAt>(entries: Entries<'_, R>) -> Vec<String> {
+        entries.map(|e| e.unwrap().1.url).collect()
+    }
+
+    #[test]
+    fn test_path_and_title_order() {
+        let archive = test_archive();
+        assert_eq!(urls(archive.entries_by_path()), vec!["a", "b", "c", "Title", "mainPage"]);
+        assert_eq!(archive.entries_by_path().size_hint(), (5, Some(5)));
+
+        let entries: Vec<_> = archive.entries_by_title().unwrap().map(|e| e.unwrap().0).collect();
+        assert_eq!(entries, vec![1, 2, 0, 3, 4]);
+    }
+
+    #[test]
+    fn test_namespace() {
+        let archive = test_archive();
+        assert_eq!(urls(archive.entries_by_path().namespace('C').unwrap()), vec!["a", "b", "c"]);
+        assert_eq!(urls(archive.entries_by_title().unwrap().namespace('C').unwrap()), vec!["b", "c", "a"]);
+        assert_eq!(urls(archive.entries_by_path().namespace('M').unwrap()), vec!["Title"]);
+        assert_eq!(urls(archive.entries_by_path().namespace('X').unwrap()), Vec::<String>::new());
+        assert_eq!(urls(archive.entries_by_path().namespace('A').unwrap()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_filters() {
+        let archive = test_archive();
+        assert_eq!(urls(archive.entries_by_path().content_only()), vec!["a", "b", "c"]);
+        assert_eq!(urls(archive.entries_by_path().non_redirects()), vec!["a", "b", "Title", "mainPage"]);
+        assert_eq!(urls(archive.entries_by_title().unwrap().content_only().non_redirects()), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn test_legacy_content_only() {
+        let mut data = build_zim(&[
+            blob('-', "style.css", "", b""),
+            blob('A', "Foo", "", b""),
+            blob('I', "logo.png", "", b""),
+            blob('M', "Title", "", b""),
+        ], 0);
+        set_version(&mut data, 6, 0);
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+        assert_eq!(urls(archive.entries_by_path().content_only()), vec!["style.css", "Foo", "logo.png"]);
+    }
+
+    #[test]
+    fn test_missing_title_index() {
+        let mut data = build_zim(&[blob('C', "a", "", b"")], 0);
+        data[40..48].copy_from_slice(&u64::MAX.to_le_bytes());
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+        assert!(matches!(archive.entries_by_title(), Err(ZimError::MissingTitleIndex)));
+    }
+}
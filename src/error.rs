@@ -0,0 +1,127 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use crate::cluster::Compression;
+
+/// The part of a zim file being read when an error occurred.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Structure {
+    Header,
+    MimeList,
+    PathPointerList,
+    TitlePointerList,
+    ClusterPointerList,
+    Dirent,
+    Cluster,
+    Checksum,
+}
+
+impl fmt::Display for Structure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Structure::Header => "header",
+            Structure::MimeList => "mime list",
+            Structure::PathPointerList => "path pointer list",
+            Structure::TitlePointerList => "title pointer list",
+            Structure::ClusterPointerList => "cluster pointer list",
+            Structure::Dirent => "dirent",
+            Structure::Cluster => "cluster",
+            Structure::Checksum => "checksum",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Error returned by all parsing and lookup operations.
+///
+/// `offset` fields hold the file offset of the structure being parsed. Parsers called
+/// directly on a reader (e.g. `Dirent::parse`) report offsets relative to where they started.
+#[derive(Debug)]
+pub enum ZimError {
+    FileNotFound(PathBuf),
+    Io { structure: Structure, offset: u64, source: io::Error },
+    InvalidMagicNumber { found: u32 },
+    UnsupportedVersion { major: u16, minor: u16 },
+    InvalidCompression { offset: u64, value: u8 },
+    Decompression { offset: u64, compression: Compression, message: String },
+    TooManyBlobs { offset: u64, count: u64 },
+    InvalidBlobOffsets { offset: u64 },
+    TruncatedCluster { offset: u64, expected: u64, found: u64 },
+    InvalidUtf8 { structure: Structure, offset: u64 },
+    InvalidMimeList { offset: u64 },
+    OutOfBounds { structure: Structure, index: u64, count: u64 },
+    MissingTitleIndex,
+    MissingChecksum,
+    NoContent { path: String },
+    InvalidMetadata { key: String },
+    RedirectLoop { index: u32 },
+    RedirectOutOfRange { index: u32 },
+    RedirectTooDeep { max_depth: usize },
+    DuplicateEntry { namespace: char, path: String },
+    RedirectTargetNotFound { namespace: char, path: String },
+    ContentRead { path: String, source: io::Error },
+    UnsupportedCompression { compression: Compression },
+    InvalidField { structure: Structure, field: &'static str },
+}
+
+impl ZimError {
+    pub(crate) fn io(structure: Structure, source: io::Error) -> Self {
+        ZimError::Io { structure, offset: 0, source }
+    }
+
+    /// Sets the file offset of the structure the error was raised for.
+    pub(crate) fn at(mut self, structure_offset: u64) -> Self {
+        match &mut self {
+            ZimError::Io { offset, .. }
+            | ZimError::InvalidCompression { offset, .. }
+            | ZimError::Decompression { offset, .. }
+            | ZimError::TooManyBlobs { offset, .. }
+            | ZimError::InvalidBlobOffsets { offset }
+            | ZimError::TruncatedCluster { offset, .. }
+            | ZimError::InvalidUtf8 { offset, .. }
+            | ZimError::InvalidMimeList { offset } => *offset = structure_offset,
+            _ => {}
+        }
+        self
+    }
+}
+
+impl fmt::Display for ZimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZimError::FileNotFound(path) => write!(f, "File doesn't exist: {}", path.display()),
+            ZimError::Io { structure, offset, source } => write!(f, "Failed to read {} at offset {}: {}", structure, offset, source),
+            ZimError::InvalidMagicNumber { found } => write!(f, "Invalid magic number: {:#010x}", found),
+            ZimError::UnsupportedVersion { major, minor } => write!(f, "Unsupported zim version: {}.{}", major, minor),
+            ZimError::InvalidCompression { offset, value } => write!(f, "Invalid compression type {} in cluster at offset {}", value, offset),
+            ZimError::Decompression { offset, compression, message } => write!(f, "Failed to decompress {:?} cluster at offset {}: {}", compression, offset, message),
+            ZimError::TooManyBlobs { offset, count } => write!(f, "Too many blobs in cluster at offset {}: {}", offset, count),
+            ZimError::InvalidBlobOffsets { offset } => write!(f, "Blob offsets are not in ascending order in cluster at offset {}", offset),
+            ZimError::TruncatedCluster { offset, expected, found } => write!(f, "Cluster at offset {} truncated: expected {} bytes, got {}", offset, expected, found),
+            ZimError::InvalidUtf8 { structure, offset } => write!(f, "Invalid UTF-8 in {} at offset {}", structure, offset),
+            ZimError::InvalidMimeList { offset } => write!(f, "Invalid mime list at offset {}", offset),
+            ZimError::OutOfBounds { structure, index, count } => write!(f, "Index {} out of range for {} of {} entries", index, structure, count),
+            ZimError::MissingTitleIndex => write!(f, "Archive has no title index"),
+            ZimError::MissingChecksum => write!(f, "Archive has no checksum"),
+            ZimError::NoContent { path } => write!(f, "Dirent has no content: {}", path),
+            ZimError::InvalidMetadata { key } => write!(f, "Invalid value for metadata {}", key),
+            ZimError::RedirectLoop { index } => write!(f, "Redirect loop detected at entry {}", index),
+            ZimError::RedirectOutOfRange { index } => write!(f, "Redirect index out of range: {}", index),
+            ZimError::RedirectTooDeep { max_depth } => write!(f, "Redirect chain longer than {} entries", max_depth),
+            ZimError::DuplicateEntry { namespace, path } => write!(f, "Duplicate entry: {}/{}", namespace, path),
+            ZimError::RedirectTargetNotFound { namespace, path } => write!(f, "Redirect target not found: {}/{}", namespace, path),
+            ZimError::ContentRead { path, source } => write!(f, "Failed to read content of {}: {}", path, source),
+            ZimError::UnsupportedCompression { compression } => write!(f, "{:?} compressed clusters are not supported", compression),
+            ZimError::InvalidField { structure, field } => write!(f, "Value of {} can't be written in a {}", field, structure),
+        }
+    }
+}
+
+impl std::error::Error for ZimError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ZimError::Io { source, .. } | ZimError::ContentRead { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
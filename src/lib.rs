@@ -1,18 +1,79 @@
+//! Parser for [zim](https://wiki.openzim.org/wiki/ZIM_file_format) files.
+//!
+//! Two ways to read an archive:
+//! * [`parse_zim`] / [`ZimFile::parse_bytes`] read everything upfront, fine for small files.
+//! * [`open_zim`] / [`Archive::new`] only read the header and mime list, and the rest on demand.
+//!   An `Archive` reads through [`ReadAt`], so it can be shared between threads.
+//!
+//! [`ZimWriter`] goes the other way and builds a zim file from items and redirects.
+//!
+//! The building blocks are re-exported here so they can be used in downstream signatures:
+//! [`ZimHeader`] for the header, [`Dirent`] / [`DirentData`] for entries,
+//! [`Cluster`] / [`Compression`] for the clusters holding item data, and [`ZimError`] for failures.
+//!
+//! ```no_run
+//! use libzim_rs::{parse_zim, Dirent, DirentData, ZimFile};
+//!
+//! fn articles(zim: &ZimFile) -> impl Iterator<Item = &Dirent> {
+//!     zim.dirents.iter().filter(|d| matches!(d.data, DirentData::Content { .. }))
+//! }
+//!
+//! let zim = parse_zim("/tmp/euler.zim").unwrap();
+//! for dirent in articles(&zim) {
+//!     println!("{}: {} bytes", dirent.get_title(), zim.get_blob(dirent).unwrap().len());
+//! }
+//! ```
+
 use std::path::Path;
 use std::fs::File;
 
 mod zimfile;
+mod archive;
 mod zimheader;
 mod cluster;
 mod dirent;
+mod search;
+mod redirect;
+mod md5;
+mod error;
+mod metadata;
+mod cache;
+mod read_at;
+mod blob_reader;
+mod namespace;
+mod entries;
+mod writer;
+#[cfg(test)]
+mod test_util;
 
-pub use zimfile::*;
+pub use zimfile::ZimFile;
+pub use archive::Archive;
+pub use cache::{CacheConfig, CacheStats};
+pub use read_at::{FileReader, ReadAt, SeekReader};
+pub use blob_reader::BlobReader;
+pub use entries::{Entries, EntryOrder};
+pub use writer::{CompressionHint, Content, ContentProvider, FileContent, WriterConfig, ZimWriter, DEFAULT_CLUSTER_SIZE, DEFAULT_ZSTD_LEVEL};
+pub use namespace::{NamespaceScheme, CONTENT_NAMESPACE, LEGACY_CONTENT_NAMESPACES};
+pub use zimheader::{ZimHeader, HEADER_SIZE, LATEST_MINOR_VERSION, NO_MAIN_PAGE, SUPPORTED_MAJOR_VERSIONS, ZIM_MAGIC_NUMBER};
+pub use dirent::{Dirent, DirentData, DELETED_MIME_TYPE, LINK_TARGET_MIME_TYPE, REDIRECT_MIME_TYPE};
+pub use cluster::{Cluster, Compression, ZstdLevel};
+pub use redirect::DEFAULT_MAX_REDIRECTS;
+pub use error::{Structure, ZimError};
+pub use metadata::{MetadataDate, DEFAULT_ILLUSTRATION_SIZE, METADATA_NAMESPACE};
 
-pub fn parse_zim(file_path: &str) -> Result<ZimFile, String> {
+pub fn parse_zim(file_path: &str) -> Result<ZimFile, ZimError> {
     let p = Path::new(file_path);
-    if !p.exists() { return Err("File doesn't exist!".to_string()); }
+    if !p.exists() { return Err(ZimError::FileNotFound(p.to_path_buf())); }
 
-    let mut fr = File::open(p).map_err(|e| e.to_string())?;
+    let mut fr = File::open(p).map_err(|e| ZimError::io(Structure::Header, e))?;
     let z = ZimFile::parse_bytes(&mut fr)?;
     Ok(z)
 }
+
+pub fn open_zim(file_path: &str) -> Result<Archive<FileReader>, ZimError> {
+    let p = Path::new(file_path);
+    if !p.exists() { return Err(ZimError::FileNotFound(p.to_path_buf())); }
+
+    let fr = File::open(p).map_err(|e| ZimError::io(Structure::Header, e))?;
+    Archive::new(read_at::file_reader(fr))
+}
@@ -0,0 +1,270 @@
+use std::fmt;
+use crate::archive::Archive;
+use crate::error::ZimError;
+use crate::search::binary_search_by;
+use crate::read_at::ReadAt;
+
+/// Namespace holding the archive metadata.
+pub const METADATA_NAMESPACE: char = 'M';
+
+/// Size of the illustration every archive is expected to have, and of the legacy favicon.
+pub const DEFAULT_ILLUSTRATION_SIZE: u32 = 48;
+
+/// Favicon location in archives predating the `Illustration_*` metadata.
+const LEGACY_FAVICON_NAMESPACE: char = '-';
+const LEGACY_FAVICON_PATH: &str = "favicon";
+
+/// Value of the `Date` metadata (`YYYY-MM-DD`).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MetadataDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl MetadataDate {
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().splitn(3, '-');
+        let year = parts.next()?;
+        let month = parts.next()?;
+        let day = parts.next()?;
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+
+        let date = MetadataDate {
+            year: year.parse().ok()?,
+            month: month.parse().ok()?,
+            day: day.parse().ok()?,
+        };
+        if !(1..=12).contains(&date.month) || !(1..=31).contains(&date.day) {
+            return None;
+        }
+        Some(date)
+    }
+}
+
+impl fmt::Display for MetadataDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl<R: ReadAt> Archive<R> {
+    /// Keys of all `M/` entries, in path order.
+    pub fn metadata_keys(&self) -> Result<Vec<String>, ZimError> {
+        let start = binary_search_by(self.entry_count(), |index| {
+            Ok(self.get_dirent(index)?.cmp_path(METADATA_NAMESPACE, ""))
+        })?;
+        let start = start.unwrap_or_else(|insert_at| insert_at);
+
+        let mut keys = Vec::new();
+        for index in start..self.entry_count() {
+            let dirent = self.get_dirent(index)?;
+            if dirent.namespace != METADATA_NAMESPACE {
+                break;
+            }
+            keys.push(dirent.url);
+        }
+        Ok(keys)
+    }
+
+    /// Raw value of the metadata `key`, `None` if the archive doesn't have it.
+    pub fn metadata(&self, key: &str) -> Result<Option<Vec<u8>>, ZimError> {
+        let dirent = match self.find_by_path(METADATA_NAMESPACE, key)? {
+            Some((_, dirent)) => self.resolve_redirect(dirent)?,
+            None => return Ok(None),
+        };
+        self.get_blob(&dirent).map(Some)
+    }
+
+    /// Value of the metadata `key` as text.
+    pub fn metadata_str(&self, key: &str) -> Result<Option<String>, ZimError> {
+        match self.metadata(key)? {
+            Some(bytes) => String::from_utf8(bytes)
+                .map(Some)
+                .map_err(|_| ZimError::InvalidMetadata { key: key.to_string() }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn title(&self) -> Result<Option<String>, ZimError> {
+        self.metadata_str("Title")
+    }
+
+    pub fn description(&self) -> Result<Option<String>, ZimError> {
+        self.metadata_str("Description")
+    }
+
+    pub fn creator(&self) -> Result<Option<String>, ZimError> {
+        self.metadata_str("Creator")
+    }
+
+    pub fn publisher(&self) -> Result<Option<String>, ZimError> {
+        self.metadata_str("Publisher")
+    }
+
+    pub fn name(&self) -> Result<Option<String>, ZimError> {
+        self.metadata_str("Name")
+    }
+
+    pub fn flavour(&self) -> Result<Option<String>, ZimError> {
+        self.metadata_str("Flavour")
+    }
+
+    pub fn license(&self) -> Result<Option<String>, ZimError> {
+        self.metadata_str("License")
+    }
+
+    /// ISO 639-3 codes from the comma separated `Language` metadata.
+    pub fn languages(&self) -> Result<Vec<String>, ZimError> {
+        Ok(split_list(self.metadata_str("Language")?, ','))
+    }
+
+    /// Tags from the semicolon separated `Tags` metadata.
+    pub fn tags(&self) -> Result<Vec<String>, ZimError> {
+        Ok(split_list(self.metadata_str("Tags")?, ';'))
+    }
+
+    /// Sizes of the square illustrations available, in ascending order.
+    ///
+    /// A legacy `-/favicon` is reported as the default size.
+    pub fn illustration_sizes(&self) -> Result<Vec<u32>, ZimError> {
+        let mut sizes: Vec<u32> = self.metadata_keys()?
+            .iter()
+            .filter_map(|key| parse_illustration_key(key))
+            .collect();
+        if !sizes.contains(&DEFAULT_ILLUSTRATION_SIZE) && self.find_by_path(LEGACY_FAVICON_NAMESPACE, LEGACY_FAVICON_PATH)?.is_some() {
+            sizes.push(DEFAULT_ILLUSTRATION_SIZE);
+        }
+        sizes.sort_unstable();
+        sizes.dedup();
+        Ok(sizes)
+    }
+
+    /// PNG data of the `size`x`size` illustration, `None` if the archive doesn't have one.
+    ///
+    /// For the default size, falls back to the legacy `-/favicon` entry.
+    pub fn illustration(&self, size: u32) -> Result<Option<Vec<u8>>, ZimError> {
+        if let Some(data) = self.metadata(&format!("Illustration_{}x{}@1", size, size))? {
+            return Ok(Some(data));
+        }
+        if size != DEFAULT_ILLUSTRATION_SIZE {
+            return Ok(None);
+        }
+        match self.find_by_path(LEGACY_FAVICON_NAMESPACE, LEGACY_FAVICON_PATH)? {
+            Some((_, dirent)) => {
+                let dirent = self.resolve_redirect(dirent)?;
+                self.get_blob(&dirent).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn date(&self) -> Result<Option<MetadataDate>, ZimError> {
+        match self.metadata_str("Date")? {
+            Some(value) => MetadataDate::parse(&value)
+                .map(Some)
+                .ok_or(ZimError::InvalidMetadata { key: "Date".to_string() }),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Size from an `Illustration_<width>x<height>@1` key, for square illustrations.
+fn parse_illustration_key(key: &str) -> Option<u32> {
+    let (width, height) = key.strip_prefix("Illustration_")?.strip_suffix("@1")?.split_once('x')?;
+    let width: u32 = width.parse().ok()?;
+    (height.parse::<u32>().ok()? == width).then_some(width)
+}
+
+fn split_list(value: Option<String>, separator: char) -> Vec<String> {
+    value
+        .map(|v| v.split(separator).map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::test_util::{blob, build_zim, redirect};
+    use crate::zimheader::NO_MAIN_PAGE;
+
+    fn test_archive() -> Archive<Cursor<Vec<u8>>> {
+        let data = build_zim(&[
+            blob('C', "index", "", b"<html></html>"),
+            blob('M', "Date", "", b"2024-03-15"),
+            blob('M', "Language", "", b"eng,fra"),
+            redirect('M', "Name", "", 5),
+            blob('M', "Tags", "", b"wikipedia;_pictures:no;"),
+            blob('M', "Title", "", b"Test archive"),
+            blob('W', "mainPage", "", b""),
+        ], 0);
+        Archive::new(Cursor::new(data)).unwrap()
+    }
+
+    #[test]
+    fn test_metadata_keys() {
+        let archive = test_archive();
+        assert_eq!(archive.metadata_keys().unwrap(), vec!["Date", "Language", "Name", "Tags", "Title"]);
+    }
+
+    #[test]
+    fn test_typed_metadata() {
+        let archive = test_archive();
+        assert_eq!(archive.title().unwrap().as_deref(), Some("Test archive"));
+        assert_eq!(archive.languages().unwrap(), vec!["eng", "fra"]);
+        assert_eq!(archive.tags().unwrap(), vec!["wikipedia", "_pictures:no"]);
+        assert_eq!(archive.date().unwrap(), Some(MetadataDate { year: 2024, month: 3, day: 15 }));
+        // Redirected metadata is followed
+        assert_eq!(archive.name().unwrap().as_deref(), Some("Test archive"));
+        assert_eq!(archive.description().unwrap(), None);
+        assert_eq!(archive.metadata("Creator").unwrap(), None);
+    }
+
+    #[test]
+    fn test_illustrations() {
+        let data = build_zim(&[
+            blob('M', "Illustration_48x48@1", "", b"png48"),
+            blob('M', "Illustration_96x96@1", "", b"png96"),
+            blob('M', "Illustration_96x96@2", "", b"png96@2"),
+            blob('M', "Title", "", b"t"),
+        ], NO_MAIN_PAGE);
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+
+        assert_eq!(archive.illustration_sizes().unwrap(), vec![48, 96]);
+        assert_eq!(archive.illustration(96).unwrap().unwrap(), b"png96");
+        assert_eq!(archive.illustration(48).unwrap().unwrap(), b"png48");
+        assert!(archive.illustration(128).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_legacy_favicon() {
+        let data = build_zim(&[
+            blob('-', "favicon", "", b"legacy"),
+            blob('A', "index", "", b"<html></html>"),
+        ], NO_MAIN_PAGE);
+        let archive = Archive::new(Cursor::new(data)).unwrap();
+
+        assert_eq!(archive.illustration_sizes().unwrap(), vec![DEFAULT_ILLUSTRATION_SIZE]);
+        assert_eq!(archive.illustration(48).unwrap().unwrap(), b"legacy");
+        assert!(archive.illustration(96).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_illustration_key() {
+        assert_eq!(parse_illustration_key("Illustration_48x48@1"), Some(48));
+        assert_eq!(parse_illustration_key("Illustration_48x32@1"), None);
+        assert_eq!(parse_illustration_key("Illustration_48x48@2"), None);
+        assert_eq!(parse_illustration_key("Title"), None);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(MetadataDate::parse("2021-12-01").unwrap().to_string(), "2021-12-01");
+        assert!(MetadataDate::parse("2021-13-01").is_none());
+        assert!(MetadataDate::parse("21-12-01").is_none());
+        assert!(MetadataDate::parse("yesterday").is_none());
+    }
+}
@@ -0,0 +1,67 @@
+use crate::zimheader::ZimHeader;
+
+/// Namespace of all content entries in archives using the new namespace scheme.
+pub const CONTENT_NAMESPACE: char = 'C';
+
+/// Namespaces content was split into before the new scheme: articles, images and layout files.
+pub const LEGACY_CONTENT_NAMESPACES: [char; 3] = ['A', 'I', '-'];
+
+/// How entries are laid out in namespaces, introduced with version 6.1.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NamespaceScheme {
+    /// Content spread over `LEGACY_CONTENT_NAMESPACES`.
+    Legacy,
+    /// All content in `CONTENT_NAMESPACE`.
+    New,
+}
+
+impl NamespaceScheme {
+    pub fn from_header(header: &ZimHeader) -> Self {
+        if header.major_version >= 6 && header.minor_version >= 1 {
+            NamespaceScheme::New
+        } else {
+            NamespaceScheme::Legacy
+        }
+    }
+
+    /// Namespaces searched for a content path, in lookup order.
+    pub fn content_namespaces(self) -> &'static [char] {
+        match self {
+            NamespaceScheme::Legacy => &LEGACY_CONTENT_NAMESPACES,
+            NamespaceScheme::New => &[CONTENT_NAMESPACE],
+        }
+    }
+
+    pub fn is_content_namespace(self, namespace: char) -> bool {
+        self.content_namespaces().contains(&namespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::test_util::{build_zim, set_version};
+
+    fn header(major_version: u16, minor_version: u16) -> ZimHeader {
+        let mut data = build_zim(&[], 0);
+        set_version(&mut data, major_version, minor_version);
+        ZimHeader::parse_header(&mut Cursor::new(data)).unwrap()
+    }
+
+    #[test]
+    fn test_scheme_from_version() {
+        assert_eq!(header(5, 0).namespace_scheme(), NamespaceScheme::Legacy);
+        assert_eq!(header(6, 0).namespace_scheme(), NamespaceScheme::Legacy);
+        assert_eq!(header(6, 1).namespace_scheme(), NamespaceScheme::New);
+        assert_eq!(header(6, 3).namespace_scheme(), NamespaceScheme::New);
+    }
+
+    #[test]
+    fn test_content_namespaces() {
+        assert!(NamespaceScheme::Legacy.is_content_namespace('I'));
+        assert!(!NamespaceScheme::Legacy.is_content_namespace('C'));
+        assert!(NamespaceScheme::New.is_content_namespace('C'));
+        assert!(!NamespaceScheme::New.is_content_namespace('M'));
+    }
+}
@@ -0,0 +1,189 @@
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+
+/// Positional reads: reading at an offset without moving a shared cursor,
+/// so a single source can serve reads from many threads at once.
+pub trait ReadAt {
+    /// Reads up to `buf.len()` bytes at `offset`, returning how many were read (0 at the end).
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+
+    fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read_at(buf, offset) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+                Ok(n) => {
+                    buf = &mut buf[n..];
+                    offset += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl ReadAt for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::read_at(self, buf, offset)
+    }
+}
+
+#[cfg(windows)]
+impl ReadAt for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        // seek_read moves the file cursor, but never reads relative to it.
+        std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+    }
+}
+
+/// Reader `open_zim` wraps a file in: the file itself where the platform has positional
+/// reads, a `SeekReader` elsewhere.
+#[cfg(any(unix, windows))]
+pub type FileReader = File;
+#[cfg(not(any(unix, windows)))]
+pub type FileReader = SeekReader<File>;
+
+#[cfg(any(unix, windows))]
+pub(crate) fn file_reader(file: File) -> FileReader {
+    file
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn file_reader(file: File) -> FileReader {
+    SeekReader::new(file)
+}
+
+impl ReadAt for [u8] {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let start = std::cmp::min(offset, self.len() as u64) as usize;
+        let n = std::cmp::min(buf.len(), self.len() - start);
+        buf[..n].copy_from_slice(&self[start..start + n]);
+        Ok(n)
+    }
+}
+
+impl ReadAt for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.as_slice().read_at(buf, offset)
+    }
+}
+
+/// Reads the underlying bytes, ignoring the cursor position.
+impl<T: AsRef<[u8]>> ReadAt for Cursor<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.get_ref().as_ref().read_at(buf, offset)
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for &T {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for Arc<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+}
+
+/// Adapter for sources that can only seek, e.g. a `BufReader`.
+/// Reads are serialized through a mutex around the reader.
+#[derive(Debug)]
+pub struct SeekReader<R: Read + Seek> {
+    reader: Mutex<R>,
+}
+
+impl<R: Read + Seek> SeekReader<R> {
+    pub fn new(reader: R) -> Self {
+        SeekReader { reader: Mutex::new(reader) }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<R: Read + Seek> ReadAt for SeekReader<R> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let mut reader = self.reader.lock().unwrap_or_else(|e| e.into_inner());
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read(buf)
+    }
+}
+
+/// `Read` over a `ReadAt` source, starting at a given offset.
+pub(crate) struct ReadAtCursor<'a, R: ReadAt + ?Sized> {
+    source: &'a R,
+    pos: u64,
+}
+
+impl<'a, R: ReadAt + ?Sized> ReadAtCursor<'a, R> {
+    pub fn new(source: &'a R, pos: u64) -> Self {
+        ReadAtCursor { source, pos }
+    }
+}
+
+impl<R: ReadAt + ?Sized> Read for ReadAtCursor<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.source.read_at(buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: ReadAt + ?Sized> Seek for ReadAtCursor<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = match pos {
+            SeekFrom::Start(p) => p,
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?,
+            // The length of the source is unknown
+            SeekFrom::End(_) => return Err(io::Error::from(io::ErrorKind::Unsupported)),
+        };
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice_read_at() {
+        let data = b"hello world".to_vec();
+        let mut buf = [0u8; 5];
+        assert_eq!(data.read_at(&mut buf, 6).unwrap(), 5);
+        assert_eq!(&buf, b"world");
+        assert_eq!(data.read_at(&mut buf, 9).unwrap(), 2);
+        assert_eq!(data.read_at(&mut buf, 20).unwrap(), 0);
+        assert!(data.read_exact_at(&mut buf, 8).is_err());
+    }
+
+    #[test]
+    fn test_seek_reader_and_cursor() {
+        let source = SeekReader::new(Cursor::new(b"0123456789".to_vec()));
+        let mut buf = [0u8; 3];
+        source.read_exact_at(&mut buf, 4).unwrap();
+        assert_eq!(&buf, b"456");
+
+        let mut rest = String::new();
+        ReadAtCursor::new(&source, 7).read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "789");
+    }
+
+    #[test]
+    fn test_file_read_at() {
+        let path = std::env::temp_dir().join(format!("libzim-rs-read-at-{}", std::process::id()));
+        std::fs::write(&path, b"positional").unwrap();
+        let file = File::open(&path).unwrap();
+
+        let mut buf = [0u8; 4];
+        file.read_exact_at(&mut buf, 3).unwrap();
+        assert_eq!(&buf, b"itio");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
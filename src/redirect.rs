@@ -0,0 +1,38 @@
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use crate::dirent::{Dirent, DirentData};
+use crate::error::ZimError;
+
+/// Maximum number of redirects followed by `resolve_redirect`.
+pub const DEFAULT_MAX_REDIRECTS: usize = 50;
+
+/// Well-known entry pointing to the main page in newer archives.
+pub const MAIN_PAGE_NAMESPACE: char = 'W';
+pub const MAIN_PAGE_PATH: &str = "mainPage";
+
+/// Follows redirects from `start` until a non-redirect entry is reached.
+///
+/// `get` reads the dirent at a given index; it is only called for indices below `count`.
+/// Fails with `RedirectLoop` when an entry is reached twice, `RedirectOutOfRange` for
+/// indices past `count` and `RedirectTooDeep` after `max_depth` redirects.
+pub(crate) fn resolve<D, F>(start: D, count: u32, max_depth: usize, mut get: F) -> Result<D, ZimError>
+where
+    D: Borrow<Dirent>,
+    F: FnMut(u32) -> Result<D, ZimError>,
+{
+    let mut current = start;
+    let mut visited = HashSet::new();
+    while let DirentData::Redirect { redirect_index } = current.borrow().data {
+        if visited.len() >= max_depth {
+            return Err(ZimError::RedirectTooDeep { max_depth });
+        }
+        if redirect_index >= count {
+            return Err(ZimError::RedirectOutOfRange { index: redirect_index });
+        }
+        if !visited.insert(redirect_index) {
+            return Err(ZimError::RedirectLoop { index: redirect_index });
+        }
+        current = get(redirect_index)?;
+    }
+    Ok(current)
+}
@@ -0,0 +1,228 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::ops::Range;
+use crate::dirent::Dirent;
+
+/// Above this many case variants of the prefix, the rest of it is matched by reading the
+/// remaining candidates instead of searching for every variant.
+const MAX_CASE_VARIANTS: usize = 64;
+
+/// Binary search over `count` sorted items that are only reachable through a fallible probe,
+/// e.g. dirents read from disk on demand.
+///
+/// `probe` returns how the item at the given index compares to the searched value.
+/// Mirrors `slice::binary_search_by`: `Ok(index)` of a match, or `Err(index)` where it would be inserted.
+pub(crate) fn binary_search_by<F, E>(count: u32, mut probe: F) -> Result<Result<u32, u32>, E>
+where
+    F: FnMut(u32) -> Result<Ordering, E>,
+{
+    let mut low = 0u32;
+    let mut high = count;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match probe(mid)? {
+            Ordering::Less => low = mid + 1,
+            Ordering::Greater => high = mid,
+            Ordering::Equal => return Ok(Ok(mid)),
+        }
+    }
+    Ok(Err(low))
+}
+
+/// Positions in the title index of the entries of `namespace` whose title starts with `prefix`,
+/// as disjoint ranges in title order.
+///
+/// `get` reads the dirent at a position of the title index. Titles are sorted case-sensitively,
+/// so with `ignore_case` the matches aren't contiguous: the range is narrowed one character at a
+/// time, for each case variant of it, keeping only the variants present in the archive.
+/// Past `MAX_CASE_VARIANTS` the candidate entries are filtered one by one, which keeps long
+/// prefixes from multiplying the number of searches.
+pub(crate) fn title_prefix_ranges<D, F, E>(count: u32, namespace: char, prefix: &str, ignore_case: bool, mut get: F) -> Result<Vec<Range<u32>>, E>
+where
+    D: Borrow<Dirent>,
+    F: FnMut(u32) -> Result<D, E>,
+{
+    if !ignore_case {
+        let range = prefix_range(0..count, namespace, prefix, &mut get)?;
+        return Ok(if range.is_empty() { Vec::new() } else { vec![range] });
+    }
+
+    let mut candidates = vec![(String::new(), prefix_range(0..count, namespace, "", &mut get)?)];
+    for c in prefix.chars() {
+        let variants = case_variants(c);
+        if candidates.len() * variants.len() > MAX_CASE_VARIANTS {
+            let ranges = candidates.into_iter().map(|(_, range)| range).collect();
+            return filter_ranges(ranges, namespace, prefix, &mut get);
+        }
+
+        let mut narrowed = Vec::new();
+        for (matched, range) in &candidates {
+            for variant in &variants {
+                let candidate = format!("{}{}", matched, variant);
+                let range = prefix_range(range.clone(), namespace, &candidate, &mut get)?;
+                if !range.is_empty() {
+                    narrowed.push((candidate, range));
+                }
+            }
+        }
+        candidates = narrowed;
+    }
+
+    let mut ranges: Vec<Range<u32>> = candidates.into_iter().map(|(_, range)| range).collect();
+    ranges.sort_unstable_by_key(|range| range.start);
+    Ok(ranges)
+}
+
+/// The character as is, lowercased and uppercased, without duplicates.
+fn case_variants(c: char) -> Vec<String> {
+    let mut variants: Vec<String> = vec![c.to_string(), c.to_lowercase().collect(), c.to_uppercase().collect()];
+    variants.sort_unstable();
+    variants.dedup();
+    variants
+}
+
+/// Whether `title` starts with `prefix`, each character of `prefix` matching any of its case variants.
+fn starts_with_ignore_case(title: &str, prefix: &str) -> bool {
+    let mut chars = prefix.chars();
+    match chars.next() {
+        None => true,
+        Some(c) => case_variants(c).iter().any(|variant| {
+            title.strip_prefix(variant.as_str()).is_some_and(|rest| starts_with_ignore_case(rest, chars.as_str()))
+        }),
+    }
+}
+
+/// Reads every position of `ranges`, keeping the runs of entries whose title starts with
+/// `prefix`, ignoring case.
+fn filter_ranges<D, F, E>(mut ranges: Vec<Range<u32>>, namespace: char, prefix: &str, get: &mut F) -> Result<Vec<Range<u32>>, E>
+where
+    D: Borrow<Dirent>,
+    F: FnMut(u32) -> Result<D, E>,
+{
+    ranges.sort_unstable_by_key(|range| range.start);
+    let mut matches: Vec<Range<u32>> = Vec::new();
+    for position in ranges.into_iter().flatten() {
+        let dirent = get(position)?;
+        let dirent = dirent.borrow();
+        if dirent.namespace != namespace || !starts_with_ignore_case(dirent.get_title(), prefix) {
+            continue;
+        }
+        match matches.last_mut() {
+            Some(last) if last.end == position => last.end += 1,
+            _ => matches.push(position..position + 1),
+        }
+    }
+    Ok(matches)
+}
+
+/// Sub-range of `within` whose titles start with `prefix`.
+fn prefix_range<D, F, E>(within: Range<u32>, namespace: char, prefix: &str, get: &mut F) -> Result<Range<u32>, E>
+where
+    D: Borrow<Dirent>,
+    F: FnMut(u32) -> Result<D, E>,
+{
+    let start = partition_point(within.clone(), get, |dirent| dirent.cmp_title(namespace, prefix) == Ordering::Less)?;
+    let end = partition_point(start..within.end, get, |dirent| {
+        (dirent.namespace == namespace && dirent.get_title().starts_with(prefix))
+            || dirent.cmp_title(namespace, prefix) == Ordering::Less
+    })?;
+    Ok(start..end)
+}
+
+/// First position of `within` for which `is_before` is false, `is_before` being true then false over the range.
+fn partition_point<D, F, E>(within: Range<u32>, get: &mut F, is_before: impl Fn(&Dirent) -> bool) -> Result<u32, E>
+where
+    D: Borrow<Dirent>,
+    F: FnMut(u32) -> Result<D, E>,
+{
+    let result = binary_search_by(within.end - within.start, |i| {
+        let dirent = get(within.start + i)?;
+        Ok(if is_before(dirent.borrow()) { Ordering::Less } else { Ordering::Greater })
+    })?;
+    Ok(within.start + result.unwrap_or_else(|insert_at| insert_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_search_by() {
+        let items = [1, 3, 5, 7];
+        let search = |value: i32| binary_search_by::<_, ()>(items.len() as u32, |i| Ok(items[i as usize].cmp(&value))).unwrap();
+
+        assert_eq!(search(1), Ok(0));
+        assert_eq!(search(7), Ok(3));
+        assert_eq!(search(4), Err(2));
+        assert_eq!(search(0), Err(0));
+        assert_eq!(search(8), Err(4));
+        assert_eq!(binary_search_by::<_, ()>(0, |_| Ok(Ordering::Equal)).unwrap(), Err(0));
+    }
+
+    #[test]
+    fn test_title_prefix_ranges() {
+        let dirent = |namespace: char, title: &str| Dirent {
+            mime_type: 0,
+            extra_len: 0,
+            namespace,
+            revision: 0,
+            data: crate::dirent::DirentData::Content { cluster_number: 0, blob_number: 0 },
+            url: title.to_string(),
+            title: String::new(),
+            parameter: Vec::new(),
+        };
+        // In title index order: uppercase sorts before lowercase
+        let dirents = [
+            dirent('A', "Paris"),
+            dirent('C', "PARIS"),
+            dirent('C', "Paris"),
+            dirent('C', "Pasta"),
+            dirent('C', "paris"),
+            dirent('C', "parrot"),
+            dirent('M', "Paris"),
+        ];
+        let search = |prefix: &str, ignore_case: bool| {
+            title_prefix_ranges::<_, _, ()>(dirents.len() as u32, 'C', prefix, ignore_case, |i| Ok(&dirents[i as usize])).unwrap()
+        };
+
+        assert_eq!(search("Pa", false), vec![2..4]);
+        assert_eq!(search("par", false), vec![4..6]);
+        assert_eq!(search("Paris", false), vec![2..3]);
+        assert_eq!(search("", false), vec![1..6]);
+        assert!(search("Lyon", false).is_empty());
+
+        assert_eq!(search("paris", true), vec![1..2, 2..3, 4..5]);
+        assert_eq!(search("PA", true), vec![1..2, 2..4, 4..6]);
+        assert!(search("lyon", true).is_empty());
+    }
+
+    #[test]
+    fn test_long_prefix_ignore_case() {
+        let mut rng = crate::test_util::Rng::new(0x5ea7);
+        let mut titles: Vec<String> = (0..500)
+            .map(|_| (0..40).map(|_| if rng.below(2) == 0 { 'a' } else { 'A' }).collect())
+            .collect();
+        titles.push("ab".to_string());
+        titles.sort();
+        titles.dedup();
+        let dirents: Vec<Dirent> = titles.iter().map(|title| Dirent {
+            mime_type: 0,
+            extra_len: 0,
+            namespace: 'C',
+            revision: 0,
+            data: crate::dirent::DirentData::Content { cluster_number: 0, blob_number: 0 },
+            url: title.clone(),
+            title: String::new(),
+            parameter: Vec::new(),
+        }).collect();
+
+        let mut reads = 0;
+        let ranges = title_prefix_ranges::<_, _, ()>(dirents.len() as u32, 'C', &"a".repeat(30), true, |i| {
+            reads += 1;
+            Ok(&dirents[i as usize])
+        }).unwrap();
+        let expected: Vec<u32> = (0..dirents.len() as u32).filter(|&i| dirents[i as usize].url.len() == 40).collect();
+        assert_eq!(ranges.into_iter().flatten().collect::<Vec<_>>(), expected);
+        assert!(reads < 5 * dirents.len(), "{} reads", reads);
+    }
+}
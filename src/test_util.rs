@@ -0,0 +1,188 @@
+// Helpers to assemble small in-memory zim files for tests.
+
+use crate::zimheader::{ZimHeader, HEADER_SIZE, ZIM_MAGIC_NUMBER};
+use crate::dirent::{Dirent, DirentData, REDIRECT_MIME_TYPE};
+use crate::cluster::{Cluster, Compression, ZstdLevel};
+use crate::md5::md5;
+
+pub enum TestContent {
+    Blob(Vec<u8>),
+    Redirect(u32),
+}
+
+pub struct TestEntry {
+    pub namespace: char,
+    pub url: String,
+    pub title: String,
+    pub mime_type: u16,
+    pub content: TestContent,
+}
+
+pub fn blob(namespace: char, url: &str, title: &str, data: &[u8]) -> TestEntry {
+    TestEntry {
+        namespace,
+        url: url.to_string(),
+        title: title.to_string(),
+        mime_type: 0,
+        content: TestContent::Blob(data.to_vec()),
+    }
+}
+
+pub fn redirect(namespace: char, url: &str, title: &str, target: u32) -> TestEntry {
+    TestEntry {
+        namespace,
+        url: url.to_string(),
+        title: title.to_string(),
+        mime_type: REDIRECT_MIME_TYPE,
+        content: TestContent::Redirect(target),
+    }
+}
+
+/// Builds a zim file with the entries in the given (path) order.
+/// All blobs are stored in a single uncompressed cluster.
+pub fn build_zim(entries: &[TestEntry], main_page: u32) -> Vec<u8> {
+    let mime_list = b"text/html\0\0";
+    let count = entries.len() as u64;
+
+    let mime_list_pos = HEADER_SIZE as u64;
+    let path_ptr_pos = mime_list_pos + mime_list.len() as u64;
+    let title_idx_pos = path_ptr_pos + 8 * count;
+    let cluster_ptr_pos = title_idx_pos + 4 * count;
+    let dirents_pos = cluster_ptr_pos + 8;
+
+    let mut dirents = Vec::new();
+    let mut dirent_pointers = Vec::new();
+    let mut blobs: Vec<&[u8]> = Vec::new();
+    for entry in entries {
+        dirent_pointers.push(dirents_pos + dirents.len() as u64);
+        let data = match &entry.content {
+            TestContent::Blob(data) => {
+                blobs.push(data);
+                DirentData::Content { cluster_number: 0, blob_number: blobs.len() as u32 - 1 }
+            }
+            TestContent::Redirect(target) => DirentData::Redirect { redirect_index: *target },
+        };
+        let dirent = Dirent {
+            mime_type: entry.mime_type,
+            extra_len: 0,
+            namespace: entry.namespace,
+            revision: 0,
+            data,
+            url: entry.url.clone(),
+            title: entry.title.clone(),
+            parameter: Vec::new(),
+        };
+        dirents.extend(dirent.to_bytes().unwrap());
+    }
+
+    let cluster_pos = dirents_pos + dirents.len() as u64;
+    let cluster = Cluster::from_blobs(Compression::None, &blobs).to_bytes(ZstdLevel::Fastest).unwrap();
+    let checksum_pos = cluster_pos + cluster.len() as u64;
+
+    let header = ZimHeader {
+        magic_number: ZIM_MAGIC_NUMBER,
+        major_version: 6,
+        minor_version: 1,
+        uuid: [0; 16],
+        article_count: count as u32,
+        cluster_count: 1,
+        path_ptr_pos,
+        title_idx_pos,
+        cluster_ptr_pos,
+        mime_list_pos,
+        main_page,
+        layout_page: u32::MAX,
+        checksum_pos,
+    };
+    let mut data = header.to_bytes().unwrap();
+    data.extend_from_slice(mime_list);
+    for p in dirent_pointers {
+        data.extend_from_slice(&p.to_le_bytes());
+    }
+    for i in title_order(entries) {
+        data.extend_from_slice(&i.to_le_bytes());
+    }
+    data.extend_from_slice(&cluster_pos.to_le_bytes());
+    data.extend(dirents);
+    data.extend(cluster);
+    let checksum = md5(&data);
+    data.extend_from_slice(&checksum);
+    data
+}
+
+/// Overwrites the version of a zim built by `build_zim`, keeping its checksum valid.
+pub fn set_version(data: &mut Vec<u8>, major_version: u16, minor_version: u16) {
+    data[4..6].copy_from_slice(&major_version.to_le_bytes());
+    data[6..8].copy_from_slice(&minor_version.to_le_bytes());
+    data.truncate(data.len() - 16);
+    let checksum = md5(data);
+    data.extend_from_slice(&checksum);
+}
+
+/// Converts a zim built by `build_zim` to the old layout: a 72 bytes header without
+/// checksum position, and no checksum at the end.
+pub fn remove_checksum(data: &[u8]) -> Vec<u8> {
+    let header = ZimHeader::parse_header(&mut &data[..]).unwrap();
+    let shift = |pos: u64| pos - 8;
+    let mut out = header.clone();
+    out.path_ptr_pos = shift(header.path_ptr_pos);
+    out.title_idx_pos = shift(header.title_idx_pos);
+    out.cluster_ptr_pos = shift(header.cluster_ptr_pos);
+    out.mime_list_pos = 72;
+    out.checksum_pos = 0;
+    let mut old = out.to_bytes().unwrap();
+    old.extend_from_slice(&data[HEADER_SIZE..data.len() - 16]);
+
+    // Pointers to dirents and clusters move with the data.
+    let mut shift_pointer = |pos: u64| {
+        let pos = pos as usize - 8;
+        let value = u64::from_le_bytes(old[pos..pos + 8].try_into().unwrap());
+        old[pos..pos + 8].copy_from_slice(&shift(value).to_le_bytes());
+    };
+    for i in 0..header.article_count as u64 {
+        shift_pointer(header.path_ptr_pos + 8 * i);
+    }
+    for i in 0..header.cluster_count as u64 {
+        shift_pointer(header.cluster_ptr_pos + 8 * i);
+    }
+    old
+}
+
+fn title_order(entries: &[TestEntry]) -> Vec<u32> {
+    let title = |e: &TestEntry| if e.title.is_empty() { e.url.clone() } else { e.title.clone() };
+    let mut order: Vec<u32> = (0..entries.len() as u32).collect();
+    order.sort_by_key(|&i| (entries[i as usize].namespace, title(&entries[i as usize])));
+    order
+}
+
+/// Small deterministic generator (xorshift64*) for the round-trip tests.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// Uniform-ish value in `0..bound`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+
+    /// Printable string without NUL, with some non-ASCII characters.
+    pub fn string(&mut self, max_len: u64) -> String {
+        const CHARS: &[char] = &['a', 'Z', '0', '/', '.', ' ', '_', 'é', 'ß', '字', '🦀'];
+        let len = self.below(max_len + 1);
+        (0..len).map(|_| CHARS[self.below(CHARS.len() as u64) as usize]).collect()
+    }
+}
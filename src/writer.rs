@@ -0,0 +1,877 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::collections::hash_map::RandomState;
+use std::fs::OpenOptions;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::cluster::{Cluster, Compression, ZstdLevel};
+use crate::dirent::{Dirent, DirentData, DELETED_MIME_TYPE, REDIRECT_MIME_TYPE};
+use crate::error::{Structure, ZimError};
+use crate::md5::Md5;
+use crate::metadata::METADATA_NAMESPACE;
+use crate::namespace::CONTENT_NAMESPACE;
+use crate::redirect::{MAIN_PAGE_NAMESPACE, MAIN_PAGE_PATH};
+use crate::zimheader::{ZimHeader, HEADER_SIZE, NO_MAIN_PAGE, ZIM_MAGIC_NUMBER};
+
+/// Uncompressed size above which a cluster is closed and a new one started.
+pub const DEFAULT_CLUSTER_SIZE: u64 = 2 * 1024 * 1024;
+/// Zstd level of the compressed clusters, the best the pure Rust encoder implements.
+pub const DEFAULT_ZSTD_LEVEL: ZstdLevel = ZstdLevel::Fastest;
+
+/// Version written by `ZimWriter`: 6.1, the new namespace scheme.
+const MAJOR_VERSION: u16 = 6;
+const MINOR_VERSION: u16 = 1;
+
+const CHECKSUM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Settings of a `ZimWriter`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct WriterConfig {
+    /// Uncompressed size above which a cluster is closed and a new one started.
+    pub cluster_size: u64,
+    /// Zstd level of the clusters holding compressible items, `None` stores them in
+    /// uncompressed clusters.
+    pub zstd_level: Option<ZstdLevel>,
+    /// Number of threads compressing clusters, 1 compresses them on the writing thread.
+    /// Clusters are still written in order, the output doesn't depend on it.
+    pub threads: usize,
+}
+
+impl Default for WriterConfig {
+    fn default() -> Self {
+        WriterConfig {
+            cluster_size: DEFAULT_CLUSTER_SIZE,
+            zstd_level: Some(DEFAULT_ZSTD_LEVEL),
+            threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        }
+    }
+}
+
+/// Whether the data of an item is worth compressing, which decides the cluster it goes in.
+/// Items of both kinds are never mixed, so already compressed media isn't compressed again.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CompressionHint {
+    Compressible,
+    Incompressible,
+}
+
+impl CompressionHint {
+    /// Media and archive formats are already compressed, anything else (text, html, css,
+    /// javascript, svg...) is assumed to compress well.
+    pub fn from_mime_type(mime_type: &str) -> Self {
+        let mime_type = mime_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        let compressed_media = ["image/", "video/", "audio/"].iter().any(|prefix| mime_type.starts_with(prefix))
+            && mime_type != "image/svg+xml"
+            && mime_type != "image/bmp";
+        let compressed_format = matches!(
+            mime_type.as_str(),
+            "application/zip" | "application/gzip" | "application/x-xz" | "application/zstd"
+                | "application/x-bzip2" | "application/x-7z-compressed" | "application/epub+zip"
+                | "font/woff" | "font/woff2" | "application/font-woff"
+        );
+        if compressed_media || compressed_format {
+            CompressionHint::Incompressible
+        } else {
+            CompressionHint::Compressible
+        }
+    }
+}
+
+/// Supplies the data of an item when the archive is written, so it doesn't have to be
+/// held in memory until then.
+pub trait ContentProvider {
+    /// Size of the content in bytes, used to pack items into clusters before reading them.
+    fn size(&self) -> u64;
+
+    fn read_content(&self) -> io::Result<Vec<u8>>;
+}
+
+/// Content read from a file on disk.
+#[derive(Debug)]
+pub struct FileContent {
+    path: PathBuf,
+    size: u64,
+}
+
+impl FileContent {
+    pub fn new(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let size = std::fs::metadata(&path)?.len();
+        Ok(FileContent { path, size })
+    }
+}
+
+impl ContentProvider for FileContent {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read_content(&self) -> io::Result<Vec<u8>> {
+        std::fs::read(&self.path)
+    }
+}
+
+/// Data of an item added to a `ZimWriter`.
+pub enum Content {
+    Bytes(Vec<u8>),
+    Provider(Box<dyn ContentProvider>),
+}
+
+impl Content {
+    pub fn provider(provider: impl ContentProvider + 'static) -> Self {
+        Content::Provider(Box::new(provider))
+    }
+
+    fn size(&self) -> u64 {
+        match self {
+            Content::Bytes(bytes) => bytes.len() as u64,
+            Content::Provider(provider) => provider.size(),
+        }
+    }
+
+    /// Reads the data, in-memory bytes are moved out so they aren't held twice.
+    fn take(&mut self) -> io::Result<Vec<u8>> {
+        match self {
+            Content::Bytes(bytes) => Ok(std::mem::take(bytes)),
+            Content::Provider(provider) => provider.read_content(),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Content {
+    fn from(bytes: Vec<u8>) -> Self {
+        Content::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for Content {
+    fn from(bytes: &[u8]) -> Self {
+        Content::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<String> for Content {
+    fn from(text: String) -> Self {
+        Content::Bytes(text.into_bytes())
+    }
+}
+
+impl From<&str> for Content {
+    fn from(text: &str) -> Self {
+        Content::Bytes(text.as_bytes().to_vec())
+    }
+}
+
+struct WriterEntry {
+    namespace: char,
+    path: String,
+    title: String,
+    kind: EntryKind,
+}
+
+enum EntryKind {
+    Item { mime_type: String, content: Content, hint: CompressionHint },
+    Redirect { namespace: char, path: String },
+}
+
+/// Builds a zim file (version 6.1) from items and redirects.
+///
+/// Entries can be added in any order, they are sorted when the archive is written.
+/// With a fixed uuid (`set_uuid`) the same entries always give the same bytes, whatever
+/// the order they were added in and the number of threads.
+/// Items go in the 'C' namespace and metadata in 'M'; setting a main path also adds
+/// the `W/mainPage` redirect.
+/// Items are packed into zstd or uncompressed clusters depending on their `CompressionHint`,
+/// compressed on `WriterConfig::threads` threads.
+pub struct ZimWriter {
+    config: WriterConfig,
+    entries: Vec<WriterEntry>,
+    main_path: Option<String>,
+    uuid: Option<[u8; 16]>,
+}
+
+impl Default for ZimWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ZimWriter {
+    pub fn new() -> Self {
+        ZimWriter::with_config(WriterConfig::default())
+    }
+
+    pub fn with_config(config: WriterConfig) -> Self {
+        ZimWriter {
+            config,
+            entries: Vec::new(),
+            main_path: None,
+            uuid: None,
+        }
+    }
+
+    /// Adds a content item, an empty `title` defaults to the path.
+    /// Whether it gets compressed is derived from the mime type.
+    pub fn add_item(&mut self, path: &str, title: &str, mime_type: &str, content: impl Into<Content>) {
+        self.add_item_with_hint(path, title, mime_type, content, CompressionHint::from_mime_type(mime_type));
+    }
+
+    /// Like `add_item`, with an explicit compression hint.
+    pub fn add_item_with_hint(&mut self, path: &str, title: &str, mime_type: &str, content: impl Into<Content>, hint: CompressionHint) {
+        self.add_entry(CONTENT_NAMESPACE, path, title, EntryKind::Item {
+            mime_type: mime_type.to_string(),
+            content: content.into(),
+            hint,
+        });
+    }
+
+    /// Adds a redirect from `path` to the item or redirect at `target_path`.
+    pub fn add_redirect(&mut self, path: &str, title: &str, target_path: &str) {
+        self.add_entry(CONTENT_NAMESPACE, path, title, EntryKind::Redirect {
+            namespace: CONTENT_NAMESPACE,
+            path: target_path.to_string(),
+        });
+    }
+
+    /// Adds a metadata entry, e.g. `Title` or `Language`, see `Archive::metadata`.
+    pub fn add_metadata(&mut self, key: &str, value: impl Into<Content>) {
+        self.add_entry(METADATA_NAMESPACE, key, "", EntryKind::Item {
+            mime_type: "text/plain".to_string(),
+            content: value.into(),
+            hint: CompressionHint::Compressible,
+        });
+    }
+
+    /// Sets the entry the header main page points to.
+    pub fn set_main_path(&mut self, path: &str) {
+        self.main_path = Some(path.to_string());
+    }
+
+    /// Sets the uuid of the archive, a random one is generated otherwise.
+    /// Needed for reproducible output, the uuid is part of the checksummed header.
+    pub fn set_uuid(&mut self, uuid: [u8; 16]) {
+        self.uuid = Some(uuid);
+    }
+
+    fn add_entry(&mut self, namespace: char, path: &str, title: &str, kind: EntryKind) {
+        // The title is only stored when it differs from the path, like readers expect.
+        let title = if title == path { String::new() } else { title.to_string() };
+        self.entries.push(WriterEntry { namespace, path: path.to_string(), title, kind });
+    }
+
+    /// Writes the archive to a new file at `path`.
+    pub fn write_file(self, path: impl AsRef<Path>) -> Result<(), ZimError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| ZimError::io(Structure::Header, e))?;
+        self.write(&mut file)?;
+        file.sync_all().map_err(|e| ZimError::io(Structure::Checksum, e))
+    }
+
+    pub fn to_bytes(self) -> Result<Vec<u8>, ZimError> {
+        let mut out = Cursor::new(Vec::new());
+        self.write(&mut out)?;
+        Ok(out.into_inner())
+    }
+
+    /// Writes the archive to `out`, from its current position.
+    ///
+    /// The header is written last, once the cluster positions are known, and the
+    /// checksum needs the whole file: `out` is sought back and read again.
+    pub fn write<W: Read + Write + Seek>(mut self, out: &mut W) -> Result<(), ZimError> {
+        let start = out.stream_position().map_err(|e| ZimError::io(Structure::Header, e))?;
+        let mut out = OffsetWriter { inner: out, start, pos: 0 };
+
+        if let Some(path) = self.main_path.take() {
+            self.entries.push(WriterEntry {
+                namespace: MAIN_PAGE_NAMESPACE,
+                path: MAIN_PAGE_PATH.to_string(),
+                title: String::new(),
+                kind: EntryKind::Redirect { namespace: CONTENT_NAMESPACE, path },
+            });
+        }
+        let mut entries = sort_entries(self.entries)?;
+        // Entry and cluster indices are 32 bits, checked once here for all of them.
+        let article_count = u32::try_from(entries.len())
+            .map_err(|_| ZimError::InvalidField { structure: Structure::Header, field: "article_count" })?;
+
+        let mime_types: Vec<&str> = entries.iter()
+            .filter_map(|e| match &e.kind {
+                EntryKind::Item { mime_type, .. } => Some(mime_type.as_str()),
+                EntryKind::Redirect { .. } => None,
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let clusters = pack_clusters(&entries, &self.config);
+        let cluster_count = u32::try_from(clusters.len())
+            .map_err(|_| ZimError::InvalidField { structure: Structure::Header, field: "cluster_count" })?;
+        let dirents = build_dirents(&entries, &mime_types, &clusters)?;
+
+        // Fixed layout: header, mime list, path pointers, title pointers, dirents,
+        // then the clusters followed by their pointers and the checksum.
+        let mut mime_list = Vec::new();
+        for mime_type in &mime_types {
+            mime_list.extend_from_slice(mime_type.as_bytes());
+            mime_list.push(0);
+        }
+        mime_list.push(0);
+
+        let mime_list_pos = HEADER_SIZE as u64;
+        let path_ptr_pos = mime_list_pos + mime_list.len() as u64;
+        let title_idx_pos = path_ptr_pos + 8 * dirents.len() as u64;
+        let dirents_pos = title_idx_pos + 4 * dirents.len() as u64;
+
+        let mut dirent_bytes = Vec::new();
+        let mut path_pointers = Vec::with_capacity(8 * dirents.len());
+        for dirent in &dirents {
+            path_pointers.extend_from_slice(&(dirents_pos + dirent_bytes.len() as u64).to_le_bytes());
+            dirent_bytes.extend(dirent.to_bytes()?);
+        }
+        let mut title_pointers = Vec::with_capacity(4 * dirents.len());
+        for index in title_order(&dirents) {
+            title_pointers.extend_from_slice(&index.to_le_bytes());
+        }
+
+        out.write_all(Structure::Header, &[0u8; HEADER_SIZE])?;
+        out.write_all(Structure::MimeList, &mime_list)?;
+        out.write_all(Structure::PathPointerList, &path_pointers)?;
+        out.write_all(Structure::TitlePointerList, &title_pointers)?;
+        out.write_all(Structure::Dirent, &dirent_bytes)?;
+
+        // Content is read here, in cluster order, and only the compression is spread over the workers.
+        let mut cluster_pointers = Vec::with_capacity(8 * clusters.len());
+        let clusters_data = clusters.iter().map(|cluster| {
+            let mut blobs = Vec::with_capacity(cluster.items.len());
+            for &i in &cluster.items {
+                let entry = &mut entries[i];
+                if let EntryKind::Item { content, .. } = &mut entry.kind {
+                    let data = content.take().map_err(|source| ZimError::ContentRead {
+                        path: format!("{}/{}", entry.namespace, entry.path),
+                        source,
+                    })?;
+                    blobs.push(data);
+                }
+            }
+            Ok(Cluster::from_blobs(cluster.compression, &blobs))
+        });
+        // Without a level there are no zstd clusters, the default is never used.
+        let zstd_level = self.config.zstd_level.unwrap_or(DEFAULT_ZSTD_LEVEL);
+        compress_clusters(self.config.threads, zstd_level, clusters_data, |bytes| {
+            cluster_pointers.extend_from_slice(&out.pos.to_le_bytes());
+            let bytes = bytes.map_err(|e| e.at(out.pos))?;
+            out.write_all(Structure::Cluster, &bytes)
+        })?;
+        let cluster_ptr_pos = out.pos;
+        out.write_all(Structure::ClusterPointerList, &cluster_pointers)?;
+        let checksum_pos = out.pos;
+
+        let header = ZimHeader {
+            magic_number: ZIM_MAGIC_NUMBER,
+            major_version: MAJOR_VERSION,
+            minor_version: MINOR_VERSION,
+            uuid: self.uuid.unwrap_or_else(random_uuid),
+            article_count,
+            cluster_count,
+            path_ptr_pos,
+            title_idx_pos,
+            cluster_ptr_pos,
+            mime_list_pos,
+            main_page: find_entry(&entries, MAIN_PAGE_NAMESPACE, MAIN_PAGE_PATH)
+                .and_then(|i| match &dirents[i as usize].data {
+                    DirentData::Redirect { redirect_index } => Some(*redirect_index),
+                    _ => None,
+                })
+                .unwrap_or(NO_MAIN_PAGE),
+            layout_page: NO_MAIN_PAGE,
+            checksum_pos,
+        };
+        out.seek_to(0)?;
+        out.write_all(Structure::Header, &header.to_bytes()?)?;
+
+        let checksum = out.checksum(checksum_pos)?;
+        out.seek_to(checksum_pos)?;
+        out.write_all(Structure::Checksum, &checksum)?;
+        out.inner.flush().map_err(|e| ZimError::io(Structure::Checksum, e).at(checksum_pos))
+    }
+}
+
+/// Sorts entries in path order, the order of the dirents, rejecting duplicates.
+/// Paths are unique, so the result doesn't depend on the order entries were added in.
+fn sort_entries(mut entries: Vec<WriterEntry>) -> Result<Vec<WriterEntry>, ZimError> {
+    entries.sort_by(|a, b| (a.namespace, &a.path).cmp(&(b.namespace, &b.path)));
+    if let Some(pair) = entries.windows(2).find(|pair| pair[0].namespace == pair[1].namespace && pair[0].path == pair[1].path) {
+        return Err(ZimError::DuplicateEntry { namespace: pair[0].namespace, path: pair[0].path.clone() });
+    }
+    Ok(entries)
+}
+
+fn find_entry(entries: &[WriterEntry], namespace: char, path: &str) -> Option<u32> {
+    entries.binary_search_by(|e| (e.namespace, e.path.as_str()).cmp(&(namespace, path)))
+        .ok()
+        .map(|i| i as u32)
+}
+
+/// Entry indices of the items of one cluster.
+struct PackedCluster {
+    compression: Compression,
+    items: Vec<usize>,
+    size: u64,
+}
+
+/// Groups items into clusters of about `cluster_size` uncompressed bytes, in path order,
+/// keeping a separate open cluster for each compression hint.
+fn pack_clusters(entries: &[WriterEntry], config: &WriterConfig) -> Vec<PackedCluster> {
+    let compressed = if config.zstd_level.is_some() { Compression::Zstd } else { Compression::None };
+    let mut open = [
+        PackedCluster { compression: compressed, items: Vec::new(), size: 0 },
+        PackedCluster { compression: Compression::None, items: Vec::new(), size: 0 },
+    ];
+    let mut clusters = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if let EntryKind::Item { content, hint, .. } = &entry.kind {
+            let current = match hint {
+                CompressionHint::Compressible => &mut open[0],
+                CompressionHint::Incompressible => &mut open[1],
+            };
+            let size = content.size();
+            if !current.items.is_empty() && current.size + size > config.cluster_size {
+                let compression = current.compression;
+                clusters.push(std::mem::replace(current, PackedCluster { compression, items: Vec::new(), size: 0 }));
+            }
+            current.items.push(i);
+            current.size += size;
+        }
+    }
+    clusters.extend(open.into_iter().filter(|cluster| !cluster.items.is_empty()));
+    clusters
+}
+
+fn build_dirents(entries: &[WriterEntry], mime_types: &[&str], clusters: &[PackedCluster]) -> Result<Vec<Dirent>, ZimError> {
+    let mut locations = vec![(0, 0); entries.len()];
+    for (cluster_number, cluster) in clusters.iter().enumerate() {
+        for (blob_number, &i) in cluster.items.iter().enumerate() {
+            locations[i] = (cluster_number as u32, blob_number as u32);
+        }
+    }
+
+    entries.iter().enumerate().map(|(i, entry)| {
+        let (mime_type, data) = match &entry.kind {
+            EntryKind::Item { mime_type, .. } => {
+                let (cluster_number, blob_number) = locations[i];
+                // Indices from `DELETED_MIME_TYPE` up are the redirect, link target and deleted markers.
+                let mime_index = u16::try_from(mime_types.binary_search(&mime_type.as_str()).unwrap())
+                    .ok()
+                    .filter(|&index| index < DELETED_MIME_TYPE)
+                    .ok_or(ZimError::InvalidField { structure: Structure::MimeList, field: "mime type count" })?;
+                (mime_index, DirentData::Content { cluster_number, blob_number })
+            }
+            EntryKind::Redirect { namespace, path } => {
+                let redirect_index = find_entry(entries, *namespace, path)
+                    .ok_or_else(|| ZimError::RedirectTargetNotFound { namespace: *namespace, path: path.clone() })?;
+                (REDIRECT_MIME_TYPE, DirentData::Redirect { redirect_index })
+            }
+        };
+        Ok(Dirent {
+            mime_type,
+            extra_len: 0,
+            namespace: entry.namespace,
+            revision: 0,
+            data,
+            url: entry.path.clone(),
+            title: entry.title.clone(),
+            parameter: Vec::new(),
+        })
+    }).collect()
+}
+
+/// Serializes `clusters` on `threads` workers, passing the results to `write` in order.
+///
+/// At most two clusters per worker are in flight, so only a few are held in memory.
+/// Errors reading a cluster stop the writing, serialization errors are passed to `write`.
+fn compress_clusters(
+    threads: usize,
+    zstd_level: ZstdLevel,
+    mut clusters: impl Iterator<Item = Result<Cluster, ZimError>>,
+    mut write: impl FnMut(Result<Vec<u8>, ZimError>) -> Result<(), ZimError>,
+) -> Result<(), ZimError> {
+    if threads <= 1 {
+        return clusters.try_for_each(|cluster| write(cluster?.to_bytes(zstd_level)));
+    }
+
+    let (job_sender, job_receiver) = mpsc::sync_channel::<(usize, Cluster)>(threads);
+    let job_receiver = Mutex::new(job_receiver);
+    thread::scope(|scope| {
+        // Owned by the scope closure so it's dropped on an early return, stopping the workers.
+        let job_sender = job_sender;
+        let (done_sender, done_receiver) = mpsc::channel();
+        for _ in 0..threads {
+            let job_receiver = &job_receiver;
+            let done_sender = done_sender.clone();
+            scope.spawn(move || loop {
+                let job = job_receiver.lock().unwrap().recv();
+                let Ok((index, cluster)) = job else { break };
+                if done_sender.send((index, cluster.to_bytes(zstd_level))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(done_sender);
+
+        // Results come back in any order and wait in `pending` until their turn.
+        let mut pending = BTreeMap::new();
+        let (mut sent, mut written) = (0, 0);
+        let mut exhausted = false;
+        loop {
+            while !exhausted && sent - written < 2 * threads {
+                match clusters.next() {
+                    Some(cluster) => {
+                        job_sender.send((sent, cluster?)).expect("cluster workers stopped");
+                        sent += 1;
+                    }
+                    None => exhausted = true,
+                }
+            }
+            if written == sent {
+                return Ok(());
+            }
+            let (index, bytes) = done_receiver.recv().expect("cluster workers stopped");
+            pending.insert(index, bytes);
+            while let Some(bytes) = pending.remove(&written) {
+                write(bytes)?;
+                written += 1;
+            }
+        }
+    })
+}
+
+/// Dirent indices sorted by namespace and title, the order of the title index.
+/// Equal titles are kept in path order.
+fn title_order(dirents: &[Dirent]) -> Vec<u32> {
+    let mut order: Vec<u32> = (0..dirents.len() as u32).collect();
+    order.sort_by(|&a, &b| {
+        let (da, db) = (&dirents[a as usize], &dirents[b as usize]);
+        (da.namespace, da.get_title(), a).cmp(&(db.namespace, db.get_title(), b))
+    });
+    order
+}
+
+/// Random version 4 UUID, from the randomly seeded std hasher.
+fn random_uuid() -> [u8; 16] {
+    let state = RandomState::new();
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let mut uuid = [0u8; 16];
+    for (i, chunk) in uuid.chunks_mut(8).enumerate() {
+        let mut hasher = state.build_hasher();
+        hasher.write_u128(nanos);
+        hasher.write_usize(i);
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+    }
+    uuid[6] = (uuid[6] & 0x0f) | 0x40;
+    uuid[8] = (uuid[8] & 0x3f) | 0x80;
+    uuid
+}
+
+/// Output tracking the position relative to the start of the archive, for error offsets.
+struct OffsetWriter<'a, W: Read + Write + Seek> {
+    inner: &'a mut W,
+    start: u64,
+    pos: u64,
+}
+
+impl<W: Read + Write + Seek> OffsetWriter<'_, W> {
+    fn write_all(&mut self, structure: Structure, bytes: &[u8]) -> Result<(), ZimError> {
+        self.inner.write_all(bytes).map_err(|e| ZimError::io(structure, e).at(self.pos))?;
+        self.pos += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn seek_to(&mut self, pos: u64) -> Result<(), ZimError> {
+        self.inner.seek(SeekFrom::Start(self.start + pos)).map_err(|e| ZimError::io(Structure::Header, e).at(pos))?;
+        self.pos = pos;
+        Ok(())
+    }
+
+    /// MD5 of the first `size` bytes of the archive.
+    fn checksum(&mut self, size: u64) -> Result<[u8; 16], ZimError> {
+        self.seek_to(0)?;
+        let io_error = |e| ZimError::io(Structure::Checksum, e).at(size);
+        let mut hasher = Md5::new();
+        let mut buffer = vec![0u8; CHECKSUM_CHUNK_SIZE];
+        let mut done = 0u64;
+        while done < size {
+            let n = std::cmp::min(CHECKSUM_CHUNK_SIZE as u64, size - done) as usize;
+            self.inner.read_exact(&mut buffer[..n]).map_err(io_error)?;
+            hasher.update(&buffer[..n]);
+            done += n as u64;
+        }
+        self.pos = size;
+        Ok(hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::Archive;
+    use crate::md5::md5;
+    use crate::zimfile::ZimFile;
+
+    fn test_writer() -> ZimWriter {
+        let mut writer = ZimWriter::new();
+        writer.add_item("index.html", "Home", "text/html", "<html>home</html>");
+        writer.add_item("style.css", "", "text/css", "body {}");
+        writer.add_item("b/page.html", "Another page", "text/html", b"<html>b</html>".to_vec());
+        writer.add_redirect("home", "Start", "index.html");
+        writer.add_metadata("Title", "Test archive");
+        writer.add_metadata("Language", "eng");
+        writer.set_main_path("index.html");
+        writer
+    }
+
+    #[test]
+    fn test_written_archive_parses() {
+        let data = test_writer().to_bytes().unwrap();
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(data)).unwrap();
+
+        assert_eq!(zim.header.major_version, 6);
+        assert_eq!(zim.header.minor_version, 1);
+        assert_eq!(zim.mime_types, vec!["text/css", "text/html", "text/plain"]);
+        let paths: Vec<_> = zim.dirents.iter().map(|d| format!("{}/{}", d.namespace, d.url)).collect();
+        assert_eq!(paths, vec!["C/b/page.html", "C/home", "C/index.html", "C/style.css", "M/Language", "M/Title", "W/mainPage"]);
+
+        let (_, dirent) = zim.find_by_path('C', "b/page.html").unwrap();
+        assert_eq!(dirent.title, "Another page");
+        assert_eq!(zim.get_blob(dirent).unwrap(), b"<html>b</html>");
+        assert_eq!(zim.mime_types[dirent.mime_type as usize], "text/html");
+
+        let (_, style) = zim.find_by_path('C', "style.css").unwrap();
+        assert_eq!(style.get_title(), "style.css");
+
+        let (_, home) = zim.find_by_path('C', "home").unwrap();
+        assert_eq!(zim.get_blob(zim.resolve_redirect(home).unwrap()).unwrap(), b"<html>home</html>");
+        assert_eq!(zim.main_entry().unwrap().unwrap().url, "index.html");
+        assert_eq!(zim.find_by_title('C', "Start").unwrap().1.url, "home");
+    }
+
+    #[test]
+    fn test_written_archive_opens_lazily() {
+        let archive = Archive::new(test_writer().to_bytes().unwrap()).unwrap();
+        assert!(archive.verify_checksum().unwrap());
+        assert_eq!(archive.title().unwrap().as_deref(), Some("Test archive"));
+        assert_eq!(archive.languages().unwrap(), vec!["eng"]);
+        assert_eq!(archive.header.main_page_index(), archive.find_by_path('C', "index.html").unwrap().map(|(i, _)| i));
+    }
+
+    #[test]
+    fn test_multiple_clusters_and_providers() {
+        struct Repeated(u8, usize);
+        impl ContentProvider for Repeated {
+            fn size(&self) -> u64 {
+                self.1 as u64
+            }
+            fn read_content(&self) -> io::Result<Vec<u8>> {
+                Ok(vec![self.0; self.1])
+            }
+        }
+
+        let mut writer = ZimWriter::with_config(WriterConfig { cluster_size: 100, ..WriterConfig::default() });
+        for i in 0..10u8 {
+            writer.add_item(&format!("item{}", i), "", "application/octet-stream", Content::provider(Repeated(i, 60)));
+        }
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(writer.to_bytes().unwrap())).unwrap();
+
+        assert_eq!(zim.header.cluster_count, 10);
+        for (i, dirent) in zim.dirents.iter().enumerate() {
+            assert_eq!(zim.get_blob(dirent).unwrap(), vec![i as u8; 60]);
+        }
+    }
+
+    #[test]
+    fn test_output_independent_of_threads() {
+        let build = |threads| {
+            let mut writer = ZimWriter::with_config(WriterConfig { cluster_size: 500, threads, ..WriterConfig::default() });
+            writer.set_uuid([7; 16]);
+            for i in 0..40u32 {
+                let text = format!("<p>{}</p>", i).repeat(i as usize * 10);
+                writer.add_item(&format!("page{}.html", i), "", "text/html", text);
+                writer.add_item(&format!("image{}.png", i), "", "image/png", vec![i as u8; 100]);
+            }
+            writer.to_bytes().unwrap()
+        };
+
+        let expected = build(1);
+        for threads in [0, 2, 3, 8] {
+            assert!(build(threads) == expected, "output differs with {} threads", threads);
+        }
+
+        let mut writer = ZimWriter::with_config(WriterConfig { cluster_size: 500, threads: 4, ..WriterConfig::default() });
+        for i in 0..40u32 {
+            writer.add_item(&format!("page{}.html", i), "", "text/html", format!("<p>{}</p>", i).repeat(100));
+        }
+        let archive = Archive::new(writer.to_bytes().unwrap()).unwrap();
+        assert!(archive.verify_checksum().unwrap());
+        assert!(archive.header.cluster_count > 10);
+        for i in 0..40u32 {
+            let (_, dirent) = archive.find_by_path('C', &format!("page{}.html", i)).unwrap().unwrap();
+            assert_eq!(archive.get_blob(&dirent).unwrap(), format!("<p>{}</p>", i).repeat(100).as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_reproducible_output() {
+        let uuid = *b"0123456789abcdef";
+        let build = |reversed: bool| {
+            let mut writer = ZimWriter::with_config(WriterConfig { cluster_size: 50, ..WriterConfig::default() });
+            writer.set_uuid(uuid);
+            let mut items: Vec<_> = (0..20).map(|i| (format!("page{}.html", i), format!("<p>{}</p>", i))).collect();
+            if reversed {
+                items.reverse();
+            }
+            for (path, text) in items {
+                // All the same title, ordered by path in the title index.
+                writer.add_item(&path, "Same title", "text/html", text);
+            }
+            writer.add_metadata("Title", "Reproducible");
+            writer.set_main_path("page0.html");
+            writer.to_bytes().unwrap()
+        };
+
+        let first = build(false);
+        assert_eq!(md5(&first), md5(&build(false)));
+        assert_eq!(md5(&first), md5(&build(true)));
+
+        let archive = Archive::new(first).unwrap();
+        assert_eq!(archive.header.uuid, uuid);
+        assert!(archive.verify_checksum().unwrap());
+        assert_ne!(ZimWriter::new().to_bytes().unwrap()[8..24], ZimWriter::new().to_bytes().unwrap()[8..24]);
+    }
+
+    #[test]
+    fn test_compression_hint_from_mime_type() {
+        assert_eq!(CompressionHint::from_mime_type("text/html; charset=utf-8"), CompressionHint::Compressible);
+        assert_eq!(CompressionHint::from_mime_type("application/javascript"), CompressionHint::Compressible);
+        assert_eq!(CompressionHint::from_mime_type("image/svg+xml"), CompressionHint::Compressible);
+        assert_eq!(CompressionHint::from_mime_type("image/PNG"), CompressionHint::Incompressible);
+        assert_eq!(CompressionHint::from_mime_type("video/webm"), CompressionHint::Incompressible);
+        assert_eq!(CompressionHint::from_mime_type("font/woff2"), CompressionHint::Incompressible);
+    }
+
+    #[test]
+    fn test_clusters_grouped_by_hint() {
+        let config = WriterConfig { cluster_size: 1000, ..WriterConfig::default() };
+        let mut writer = ZimWriter::with_config(config);
+        writer.add_item("a.html", "", "text/html", "<p>a</p>");
+        writer.add_item("b.png", "", "image/png", vec![0x89; 600]);
+        writer.add_item("c.html", "", "text/html", "<p>c</p>");
+        writer.add_item("d.jpg", "", "image/jpeg", vec![0xff; 600]);
+        writer.add_item_with_hint("e.bin", "", "application/octet-stream", vec![1; 10], CompressionHint::Incompressible);
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(writer.to_bytes().unwrap())).unwrap();
+
+        let compressions: Vec<_> = zim.clusters.iter().map(|c| c.compression).collect();
+        assert_eq!(compressions, vec![Compression::None, Compression::Zstd, Compression::None]);
+        let cluster_of = |path| match zim.find_by_path('C', path).unwrap().1.data {
+            DirentData::Content { cluster_number, .. } => cluster_number,
+            _ => panic!("Expected content"),
+        };
+        assert_eq!([cluster_of("a.html"), cluster_of("c.html")], [1, 1]);
+        assert_eq!([cluster_of("b.png"), cluster_of("d.jpg"), cluster_of("e.bin")], [0, 2, 2]);
+        for dirent in &zim.dirents {
+            assert!(zim.get_blob(dirent).is_ok());
+        }
+
+        let mut writer = ZimWriter::with_config(WriterConfig { zstd_level: None, ..config });
+        writer.add_item("a.html", "", "text/html", "<p>a</p>");
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(writer.to_bytes().unwrap())).unwrap();
+        assert_eq!(zim.clusters[0].compression, Compression::None);
+    }
+
+    #[test]
+    fn test_zstd_level() {
+        let text = "<p>Some repeated text</p>".repeat(1000);
+        let build = |zstd_level| {
+            let mut writer = ZimWriter::with_config(WriterConfig { zstd_level, ..WriterConfig::default() });
+            writer.set_uuid([0; 16]);
+            writer.add_item("page.html", "", "text/html", text.as_str());
+            let zim = ZimFile::parse_bytes(&mut Cursor::new(writer.to_bytes().unwrap())).unwrap();
+            assert_eq!(zim.get_blob(&zim.dirents[0]).unwrap(), text.as_bytes());
+            let cluster_size = zim.header.cluster_ptr_pos - zim.cluster_pointers[0];
+            (zim.clusters[0].compression, cluster_size)
+        };
+
+        let (compression, stored) = build(None);
+        assert_eq!(compression, Compression::None);
+        let (compression, uncompressed) = build(Some(ZstdLevel::Uncompressed));
+        assert_eq!(compression, Compression::Zstd);
+        assert!(uncompressed > stored);
+        let (compression, fastest) = build(Some(ZstdLevel::Fastest));
+        assert_eq!(compression, Compression::Zstd);
+        assert!(fastest < stored / 10, "{} bytes compressed from {}", fastest, stored);
+    }
+
+    #[test]
+    fn test_file_content() {
+        let path = std::env::temp_dir().join(format!("libzim-rs-writer-{}.txt", std::process::id()));
+        std::fs::write(&path, b"from disk").unwrap();
+        let mut writer = ZimWriter::new();
+        writer.add_item("file.txt", "", "text/plain", Content::provider(FileContent::new(&path).unwrap()));
+
+        let zim_path = path.with_extension("zim");
+        writer.write_file(&zim_path).unwrap();
+        let zim = crate::parse_zim(zim_path.to_str().unwrap()).unwrap();
+        assert_eq!(zim.get_blob(&zim.dirents[0]).unwrap(), b"from disk");
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&zim_path).unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut writer = ZimWriter::new();
+        writer.add_item("a", "", "text/plain", "1");
+        writer.add_item("a", "", "text/plain", "2");
+        assert!(matches!(writer.to_bytes(), Err(ZimError::DuplicateEntry { namespace: 'C', .. })));
+
+        let mut writer = ZimWriter::new();
+        writer.add_redirect("a", "", "missing");
+        assert!(matches!(writer.to_bytes(), Err(ZimError::RedirectTargetNotFound { namespace: 'C', .. })));
+
+        let mut writer = ZimWriter::new();
+        writer.add_item("a", "", "text/plain", Content::provider(FileContent { path: PathBuf::from("/nonexistent"), size: 1 }));
+        assert!(matches!(writer.to_bytes(), Err(ZimError::ContentRead { .. })));
+
+        let mut writer = ZimWriter::with_config(WriterConfig { cluster_size: 1, threads: 4, ..WriterConfig::default() });
+        for i in 0..20 {
+            writer.add_item(&format!("{:02}", i), "", "text/plain", "text");
+        }
+        writer.add_item("10a", "", "text/plain", Content::provider(FileContent { path: PathBuf::from("/nonexistent"), size: 1 }));
+        assert!(matches!(writer.to_bytes(), Err(ZimError::ContentRead { .. })));
+    }
+
+    #[test]
+    fn test_too_many_mime_types() {
+        let build = |count: u16| {
+            let mut writer = ZimWriter::new();
+            for i in 0..count {
+                writer.add_item(&i.to_string(), "", &format!("application/x-{}", i), "");
+            }
+            writer.to_bytes()
+        };
+        assert!(build(DELETED_MIME_TYPE).is_ok());
+        assert!(matches!(build(DELETED_MIME_TYPE + 1), Err(ZimError::InvalidField { structure: Structure::MimeList, .. })));
+    }
+
+    #[test]
+    fn test_empty_archive() {
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(ZimWriter::new().to_bytes().unwrap())).unwrap();
+        assert!(zim.dirents.is_empty());
+        assert_eq!(zim.header.main_page_index(), None);
+    }
+}
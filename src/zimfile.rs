@@ -1,7 +1,11 @@
+use std::convert::Infallible;
 use std::io::{Read, Seek, SeekFrom};
 use crate::zimheader::{ZimHeader};
 use crate::cluster::Cluster;
-use crate::dirent::Dirent;
+use crate::dirent::{Dirent, DirentData};
+use crate::redirect::{self, DEFAULT_MAX_REDIRECTS, MAIN_PAGE_NAMESPACE, MAIN_PAGE_PATH};
+use crate::error::{Structure, ZimError};
+use crate::search::title_prefix_ranges;
 
 #[derive(Debug)]
 pub struct ZimFile {
@@ -10,71 +14,207 @@ pub struct ZimFile {
     pub cluster_pointers: Vec<u64>,
     pub clusters: Vec<Cluster>,
     pub dirent_pointers: Vec<u64>,
-    pub dirents: Vec<Dirent>
+    pub dirents: Vec<Dirent>,
+    pub title_index: Vec<u32>,
 }
 
 impl ZimFile {
-    pub fn parse_bytes(reader: &mut (impl Read + Seek)) -> Result<Self, String> {
+    pub fn parse_bytes(reader: &mut (impl Read + Seek)) -> Result<Self, ZimError> {
         let header = ZimHeader::parse_header(reader)?;
         let mime_types = ZimFile::parse_mime_types(reader, &header)?;
         let cluster_pointers = ZimFile::parse_cluster_pointers(reader, &header)?;
-        let clusters = ZimFile::parse_clusters(reader, &cluster_pointers)?;
+        let clusters = ZimFile::parse_clusters(reader, &cluster_pointers, &header)?;
         let dirent_pointers = ZimFile::parse_dirent_pointers(reader, &header)?;
         let dirents = ZimFile::parse_dirents(reader, &dirent_pointers)?;
+        let title_index = ZimFile::parse_title_index(reader, &header)?;
 
-        Ok(ZimFile { header, mime_types, cluster_pointers, clusters, dirent_pointers, dirents })
+        Ok(ZimFile { header, mime_types, cluster_pointers, clusters, dirent_pointers, dirents, title_index })
     }
 
-    fn parse_dirent_pointers(reader: &mut (impl Read + Seek), header: &ZimHeader) -> Result<Vec<u64>, String> {
-        reader.seek(SeekFrom::Start(header.path_ptr_pos)).map_err(|e| e.to_string())?;
+    pub fn get_blob(&self, dirent: &Dirent) -> Result<&[u8], ZimError> {
+        let (cluster_number, blob_number) = match dirent.data {
+            DirentData::Content { cluster_number, blob_number } => (cluster_number, blob_number),
+            _ => return Err(ZimError::NoContent { path: dirent.url.clone() }),
+        };
+
+        let cluster = self.clusters.get(cluster_number as usize)
+            .ok_or(ZimError::OutOfBounds {
+                structure: Structure::ClusterPointerList,
+                index: cluster_number as u64,
+                count: self.clusters.len() as u64,
+            })?;
+        cluster.get_blob(blob_number as usize)
+            .ok_or(ZimError::OutOfBounds {
+                structure: Structure::Cluster,
+                index: blob_number as u64,
+                count: cluster.count() as u64,
+            })
+    }
+
+    /// Looks up an entry by namespace and path with a binary search over the dirents,
+    /// which are stored in path pointer order.
+    pub fn find_by_path(&self, namespace: char, path: &str) -> Option<(u32, &Dirent)> {
+        let index = self.dirents.binary_search_by(|d| d.cmp_path(namespace, path)).ok()?;
+        Some((index as u32, &self.dirents[index]))
+    }
+
+    /// Looks up a content entry by path, in 'C' or in the legacy content namespaces
+    /// depending on the namespace scheme of the archive.
+    pub fn find_content(&self, path: &str) -> Option<(u32, &Dirent)> {
+        self.header.namespace_scheme()
+            .content_namespaces()
+            .iter()
+            .find_map(|&namespace| self.find_by_path(namespace, path))
+    }
+
+    /// Looks up an entry by namespace and title with a binary search over the title index.
+    /// Finds nothing in archives without a title index.
+    pub fn find_by_title(&self, namespace: char, title: &str) -> Option<(u32, &Dirent)> {
+        let pos = self.title_index
+            .binary_search_by(|&i| self.dirents[i as usize].cmp_title(namespace, title))
+            .ok()?;
+        let index = self.title_index[pos];
+        Some((index, &self.dirents[index as usize]))
+    }
+
+    /// First `limit` entries of `namespace` whose title starts with `prefix`, in title order.
+    pub fn find_by_title_prefix(&self, namespace: char, prefix: &str, limit: usize) -> Vec<(u32, &Dirent)> {
+        self.title_prefix_matches(namespace, prefix, limit, false)
+    }
+
+    /// Like `find_by_title_prefix`, ignoring case.
+    pub fn find_by_title_prefix_ignore_case(&self, namespace: char, prefix: &str, limit: usize) -> Vec<(u32, &Dirent)> {
+        self.title_prefix_matches(namespace, prefix, limit, true)
+    }
+
+    fn title_prefix_matches(&self, namespace: char, prefix: &str, limit: usize, ignore_case: bool) -> Vec<(u32, &Dirent)> {
+        let dirent_at = |position: u32| &self.dirents[self.title_index[position as usize] as usize];
+        let Ok(ranges) = title_prefix_ranges::<_, _, Infallible>(self.title_index.len() as u32, namespace, prefix, ignore_case, |position| Ok(dirent_at(position)));
+        ranges.into_iter()
+            .flatten()
+            .take(limit)
+            .map(|position| (self.title_index[position as usize], dirent_at(position)))
+            .collect()
+    }
+
+    /// Follows redirects from `dirent` to the entry they finally point to.
+    pub fn resolve_redirect<'a>(&'a self, dirent: &'a Dirent) -> Result<&'a Dirent, ZimError> {
+        self.resolve_redirect_with_depth(dirent, DEFAULT_MAX_REDIRECTS)
+    }
+
+    /// Like `resolve_redirect`, following at most `max_depth` redirects.
+    pub fn resolve_redirect_with_depth<'a>(&'a self, dirent: &'a Dirent, max_depth: usize) -> Result<&'a Dirent, ZimError> {
+        redirect::resolve(dirent, self.dirents.len() as u32, max_depth, |i| Ok(&self.dirents[i as usize]))
+    }
+
+    /// The main page of the archive with redirects followed.
+    ///
+    /// Uses `header.main_page` and falls back to the `W/mainPage` entry.
+    /// Returns `None` if the archive has neither.
+    pub fn main_entry(&self) -> Result<Option<&Dirent>, ZimError> {
+        let start = match self.header.main_page_index() {
+            Some(index) => self.dirents.get(index as usize)
+                .ok_or(ZimError::OutOfBounds {
+                    structure: Structure::PathPointerList,
+                    index: index as u64,
+                    count: self.dirents.len() as u64,
+                })?,
+            None => match self.find_by_path(MAIN_PAGE_NAMESPACE, MAIN_PAGE_PATH) {
+                Some((_, dirent)) => dirent,
+                None => return Ok(None),
+            },
+        };
+        self.resolve_redirect(start).map(Some)
+    }
+
+    fn parse_dirent_pointers(reader: &mut (impl Read + Seek), header: &ZimHeader) -> Result<Vec<u64>, ZimError> {
+        let io_error = |e| ZimError::io(Structure::PathPointerList, e).at(header.path_ptr_pos);
+        reader.seek(SeekFrom::Start(header.path_ptr_pos)).map_err(io_error)?;
 
         let mut pointers = Vec::with_capacity(header.article_count as usize);
         let mut buffer = [0u8; 8];
 
         for _ in 0..header.article_count {
-             reader.read_exact(&mut buffer).map_err(|e| e.to_string())?;
+             reader.read_exact(&mut buffer).map_err(io_error)?;
              pointers.push(u64::from_le_bytes(buffer));
         }
 
         Ok(pointers)
     }
 
-    fn parse_dirents(reader: &mut (impl Read + Seek), dirent_pointers: &[u64]) -> Result<Vec<Dirent>, String> {
+    fn parse_title_index(reader: &mut (impl Read + Seek), header: &ZimHeader) -> Result<Vec<u32>, ZimError> {
+        if !header.has_title_index() {
+            return Ok(Vec::new());
+        }
+        let io_error = |e| ZimError::io(Structure::TitlePointerList, e).at(header.title_idx_pos);
+        reader.seek(SeekFrom::Start(header.title_idx_pos)).map_err(io_error)?;
+
+        let mut indices = Vec::with_capacity(header.article_count as usize);
+        let mut buffer = [0u8; 4];
+
+        for _ in 0..header.article_count {
+            reader.read_exact(&mut buffer).map_err(io_error)?;
+            let index = u32::from_le_bytes(buffer);
+            if index >= header.article_count {
+                return Err(ZimError::OutOfBounds {
+                    structure: Structure::PathPointerList,
+                    index: index as u64,
+                    count: header.article_count as u64,
+                });
+            }
+            indices.push(index);
+        }
+
+        Ok(indices)
+    }
+
+    fn parse_dirents(reader: &mut (impl Read + Seek), dirent_pointers: &[u64]) -> Result<Vec<Dirent>, ZimError> {
         let mut dirents = Vec::with_capacity(dirent_pointers.len());
         for &offset in dirent_pointers {
-            reader.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
-            let dirent = Dirent::parse(&mut *reader)?;
+            reader.seek(SeekFrom::Start(offset)).map_err(|e| ZimError::io(Structure::Dirent, e).at(offset))?;
+            let dirent = Dirent::parse(&mut *reader).map_err(|e| e.at(offset))?;
             dirents.push(dirent);
         }
         Ok(dirents)
     }
 
-    fn parse_cluster_pointers(reader: &mut (impl Read + Seek), header: &ZimHeader) -> Result<Vec<u64>, String> {
-        reader.seek(SeekFrom::Start(header.cluster_ptr_pos)).map_err(|e| e.to_string())?;
+    fn parse_cluster_pointers(reader: &mut (impl Read + Seek), header: &ZimHeader) -> Result<Vec<u64>, ZimError> {
+        let io_error = |e| ZimError::io(Structure::ClusterPointerList, e).at(header.cluster_ptr_pos);
+        reader.seek(SeekFrom::Start(header.cluster_ptr_pos)).map_err(io_error)?;
         
         let mut pointers = Vec::with_capacity(header.cluster_count as usize);
         let mut buffer = [0u8; 8];
 
         for _ in 0..header.cluster_count {
-             reader.read_exact(&mut buffer).map_err(|e| e.to_string())?;
+             reader.read_exact(&mut buffer).map_err(io_error)?;
              pointers.push(u64::from_le_bytes(buffer));
         }
         
         Ok(pointers)
     }
 
-    fn parse_clusters(reader: &mut (impl Read + Seek), cluster_pointers: &[u64]) -> Result<Vec<Cluster>, String> {
+    fn parse_clusters(reader: &mut (impl Read + Seek), cluster_pointers: &[u64], header: &ZimHeader) -> Result<Vec<Cluster>, ZimError> {
         let mut clusters = Vec::with_capacity(cluster_pointers.len());
-        for &offset in cluster_pointers {
-            reader.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
-            // Since reader is &mut (impl Read + Seek), it implements Read.
-            let cluster = Cluster::parse(&mut *reader)?;
+        for (i, &offset) in cluster_pointers.iter().enumerate() {
+            reader.seek(SeekFrom::Start(offset)).map_err(|e| ZimError::io(Structure::Cluster, e).at(offset))?;
+            // A cluster ends where the next one starts (or at the checksum for the last one).
+            // Bounding the reader keeps decompressors from running into the following data.
+            let end = match cluster_pointers.get(i + 1) {
+                Some(&next) => Some(next),
+                None if header.has_checksum() => Some(header.checksum_pos),
+                None => None,
+            };
+            let size = match end {
+                Some(end) if end > offset => end - offset,
+                _ => u64::MAX,
+            };
+            let cluster = Cluster::parse(reader.take(size)).map_err(|e| e.at(offset))?;
             clusters.push(cluster);
         }
         Ok(clusters)
     }
 
-    fn parse_mime_types(reader: &mut (impl Read + Seek), header: &ZimHeader) -> Result<Vec<String>, String> {
+    pub(crate) fn parse_mime_types(reader: &mut (impl Read + Seek), header: &ZimHeader) -> Result<Vec<String>, ZimError> {
         let mut end_pos = header.path_ptr_pos;
         if header.title_idx_pos > 0 {
             end_pos = std::cmp::min(end_pos, header.title_idx_pos);
@@ -83,7 +223,7 @@ impl ZimFile {
         
         let start_pos = header.mime_list_pos;
         if end_pos <= start_pos {
-            return Err("Invalid mime list position".to_string());
+            return Err(ZimError::InvalidMimeList { offset: start_pos });
         }
         
         let size = (end_pos - start_pos) as usize;
@@ -91,9 +231,10 @@ impl ZimFile {
             // TODO: log warning
         }
         
-        reader.seek(SeekFrom::Start(start_pos)).map_err(|e| e.to_string())?;
+        let io_error = |e| ZimError::io(Structure::MimeList, e).at(start_pos);
+        reader.seek(SeekFrom::Start(start_pos)).map_err(io_error)?;
         let mut buffer = vec![0u8; size];
-        reader.read_exact(&mut buffer).map_err(|e| e.to_string())?;
+        reader.read_exact(&mut buffer).map_err(io_error)?;
         
         let mut mime_types = Vec::new();
         let mut start = 0;
@@ -104,11 +245,11 @@ impl ZimFile {
             match buffer[start..].iter().position(|&c| c == 0) {
                 Some(len) => {
                     let s = String::from_utf8(buffer[start..start+len].to_vec())
-                        .map_err(|e| format!("Invalid UTF-8 in mime type: {}", e))?;
+                        .map_err(|_| ZimError::InvalidUtf8 { structure: Structure::MimeList, offset: start_pos })?;
                     mime_types.push(s);
                     start += len + 1;
                 },
-                None => return Err("Mime list not null terminated".to_string()),
+                None => return Err(ZimError::InvalidMimeList { offset: start_pos }),
             }
         }
         
@@ -129,8 +270,36 @@ mod tests {
         let data = vec![0u8; 79];
         let mut reader = Cursor::new(data);
         let result = ZimFile::parse_bytes(&mut reader);
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "failed to fill whole buffer");
+        match result {
+            Err(ZimError::Io { structure, offset, source }) => {
+                assert_eq!(structure, Structure::Header);
+                assert_eq!(offset, 0);
+                assert_eq!(source.kind(), std::io::ErrorKind::UnexpectedEof);
+            }
+            other => panic!("Expected header I/O error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_bytes_invalid_magic() {
+        let data = vec![0u8; HEADER_SIZE];
+        let result = ZimFile::parse_bytes(&mut Cursor::new(data));
+        assert!(matches!(result, Err(ZimError::InvalidMagicNumber { found: 0 })));
+    }
+
+    #[test]
+    fn test_errors_carry_structure_offset() {
+        use crate::test_util::{blob, build_zim};
+
+        let mut data = build_zim(&[blob('C', "a", "", b"x")], 0);
+        let cluster_ptr_pos = u64::from_le_bytes(data[48..56].try_into().unwrap()) as usize;
+        let cluster_pos = u64::from_le_bytes(data[cluster_ptr_pos..cluster_ptr_pos + 8].try_into().unwrap());
+        // Unknown compression type 9
+        data[cluster_pos as usize] = 0x09;
+
+        let err = ZimFile::parse_bytes(&mut Cursor::new(data)).unwrap_err();
+        assert!(matches!(err, ZimError::InvalidCompression { value: 9, offset } if offset == cluster_pos));
+        assert_eq!(err.to_string(), format!("Invalid compression type 9 in cluster at offset {}", cluster_pos));
     }
 
     #[test]
@@ -141,6 +310,7 @@ mod tests {
         data[0..4].copy_from_slice(&magic);
         
         // Set pointers
+        data[4..6].copy_from_slice(&6u16.to_le_bytes());
         // mime_list_pos at 80
         let mime_list_pos = 80_u64.to_le_bytes();
         data[56..64].copy_from_slice(&mime_list_pos);
@@ -177,6 +347,7 @@ mod tests {
         data[28..32].copy_from_slice(&cluster_count);
         
         // Pointers
+        data[4..6].copy_from_slice(&6u16.to_le_bytes());
         // mime_list_pos at 80
         let mime_list_pos = 80_u64.to_le_bytes();
         data[56..64].copy_from_slice(&mime_list_pos);
@@ -191,10 +362,10 @@ mod tests {
         
         // Data construction
         // 80: Mime types (dummy, 10 bytes)
-        data.extend(std::iter::repeat(0).take(10));
+        data.extend(std::iter::repeat_n(0, 10));
         
         // 90: Path pointers (dummy, 10 bytes)
-        data.extend(std::iter::repeat(0).take(10));
+        data.extend(std::iter::repeat_n(0, 10));
         
         // 100: Cluster pointers (2 * 8 = 16 bytes)
         // We need real offsets now because parse_clusters will read them.
@@ -229,7 +400,12 @@ mod tests {
         // Create Cluster 1 data at c1_offset
         // Compression: Zstd (5) | Extended (0x10) -> 0x15
         data.push(0x15);
-        // data.push(0x00); // dummy data for zstd cluster? Cluster::new only reads 1 byte for compressed currently.
+        // Compressed body: offsets table (2 * 8 = 16 bytes) + 3 bytes of blob data
+        let mut body = Vec::new();
+        body.extend_from_slice(&16u64.to_le_bytes());
+        body.extend_from_slice(&19u64.to_le_bytes());
+        body.extend_from_slice(b"xyz");
+        data.extend(ruzstd::encoding::compress_to_vec(&body[..], ruzstd::encoding::CompressionLevel::Fastest));
 
         let mut reader = Cursor::new(data);
         let zim = ZimFile::parse_bytes(&mut reader).expect("Parse failed");
@@ -243,6 +419,23 @@ mod tests {
         assert_eq!(zim.clusters[0].compression, crate::cluster::Compression::None);
         assert_eq!(zim.clusters[0].count(), 1);
         assert_eq!(zim.clusters[1].compression, crate::cluster::Compression::Zstd);
+        assert_eq!(zim.clusters[1].count(), 1);
+        assert_eq!(zim.clusters[1].get_blob_size(0), Some(3));
+
+        let dirent = |cluster_number, blob_number| Dirent {
+            mime_type: 0,
+            extra_len: 0,
+            namespace: 'C',
+            revision: 0,
+            data: DirentData::Content { cluster_number, blob_number },
+            url: "blob".to_string(),
+            title: String::new(),
+            parameter: Vec::new(),
+        };
+        assert_eq!(zim.get_blob(&dirent(0, 0)).unwrap(), &[0xAA, 0xBB]);
+        assert_eq!(zim.get_blob(&dirent(1, 0)).unwrap(), b"xyz");
+        assert!(zim.get_blob(&dirent(1, 1)).is_err());
+        assert!(zim.get_blob(&dirent(2, 0)).is_err());
     }
 
     #[test]
@@ -256,6 +449,7 @@ mod tests {
         data[24..28].copy_from_slice(&article_count);
         
         // Pointers
+        data[4..6].copy_from_slice(&6u16.to_le_bytes());
         // mime_list_pos at 80
         let mime_list_pos = 80_u64.to_le_bytes();
         data[56..64].copy_from_slice(&mime_list_pos);
@@ -270,7 +464,7 @@ mod tests {
         
         // Data construction
         // 80: Mime types (dummy, 10 bytes)
-        data.extend(std::iter::repeat(0).take(10));
+        data.extend(std::iter::repeat_n(0, 10));
         
         // 90: Dirent pointers (2 * 8 = 16 bytes)
         // Let's put dirents at the end of the current data + some offset
@@ -319,5 +513,141 @@ mod tests {
         assert_eq!(zim.dirents.len(), 2);
         assert_eq!(zim.dirents[0].url, "u0");
         assert_eq!(zim.dirents[1].url, "u1");
+
+        assert_eq!(zim.find_by_path('C', "u1").map(|(i, d)| (i, d.url.as_str())), Some((1, "u1")));
+        assert!(zim.find_by_path('C', "u2").is_none());
+        assert!(zim.find_by_path('A', "u0").is_none());
+
+        // No title index in the header
+        assert!(zim.title_index.is_empty());
+        assert!(zim.find_by_title('C', "t0").is_none());
+    }
+
+    #[test]
+    fn test_find_content() {
+        use crate::test_util::{blob, build_zim, set_version};
+
+        let entries = [
+            blob('-', "style.css", "", b"css"),
+            blob('A', "Foo", "", b"article"),
+            blob('C', "Bar", "", b"new"),
+            blob('I', "logo.png", "", b"png"),
+        ];
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(build_zim(&entries, 0))).unwrap();
+        assert_eq!(zim.find_content("Bar").unwrap().0, 2);
+        assert!(zim.find_content("Foo").is_none());
+
+        let mut data = build_zim(&entries, 0);
+        set_version(&mut data, 6, 0);
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(data)).unwrap();
+        assert_eq!(zim.find_content("Foo").unwrap().0, 1);
+        assert_eq!(zim.find_content("logo.png").unwrap().0, 3);
+        assert_eq!(zim.find_content("style.css").unwrap().0, 0);
+        assert!(zim.find_content("Bar").is_none());
+    }
+
+    #[test]
+    fn test_minor_versions() {
+        use crate::test_util::{blob, build_zim, redirect, remove_checksum, set_version};
+
+        let entries = [blob('C', "a", "Title", b"content"), redirect('C', "b", "", 0)];
+        for minor_version in 0..=crate::zimheader::LATEST_MINOR_VERSION {
+            let mut data = build_zim(&entries, 0);
+            set_version(&mut data, 6, minor_version);
+            for data in [remove_checksum(&data), data] {
+                let zim = ZimFile::parse_bytes(&mut Cursor::new(data)).unwrap();
+                assert_eq!(zim.header.minor_version, minor_version);
+                assert_eq!(zim.get_blob(zim.resolve_redirect(&zim.dirents[1]).unwrap()).unwrap(), b"content");
+                assert_eq!(zim.find_by_title('C', "Title").unwrap().0, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_title_index() {
+        use crate::test_util::{blob, build_zim};
+
+        let data = build_zim(&[
+            blob('C', "a", "Zeta", b"0"),
+            blob('C', "b", "Alpha", b"1"),
+            blob('C', "c", "", b"2"),
+        ], 0);
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(data)).expect("Parse failed");
+
+        assert_eq!(zim.title_index, vec![1, 0, 2]);
+        let (index, dirent) = zim.find_by_title('C', "Alpha").unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(dirent.url, "b");
+        assert_eq!(zim.find_by_title('C', "c").unwrap().0, 2);
+        assert!(zim.find_by_title('C', "a").is_none());
+    }
+
+    #[test]
+    fn test_find_by_title_prefix() {
+        use crate::test_util::{blob, build_zim};
+
+        let data = build_zim(&[
+            blob('C', "a", "Paris", b""),
+            blob('C', "b", "parrot", b""),
+            blob('C', "c", "Pasta", b""),
+        ], 0);
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(data)).unwrap();
+
+        let indices = |matches: Vec<(u32, &Dirent)>| matches.into_iter().map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(indices(zim.find_by_title_prefix('C', "Pa", 10)), vec![0, 2]);
+        assert_eq!(indices(zim.find_by_title_prefix_ignore_case('C', "PAR", 10)), vec![0, 1]);
+        assert_eq!(indices(zim.find_by_title_prefix_ignore_case('C', "p", 2)), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_resolve_redirect() {
+        use crate::test_util::{blob, build_zim, redirect};
+
+        let data = build_zim(&[
+            blob('C', "a", "", b"content"),
+            redirect('C', "b", "", 0),
+            redirect('C', "c", "", 1),
+            redirect('C', "d", "", 4),
+            redirect('C', "e", "", 3),
+            redirect('C', "f", "", 5),
+            redirect('C', "g", "", 42),
+        ], 0);
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(data)).expect("Parse failed");
+
+        assert_eq!(zim.resolve_redirect(&zim.dirents[0]).unwrap().url, "a");
+        assert_eq!(zim.resolve_redirect(&zim.dirents[2]).unwrap().url, "a");
+        assert_eq!(zim.get_blob(zim.resolve_redirect(&zim.dirents[1]).unwrap()).unwrap(), b"content");
+
+        assert!(matches!(zim.resolve_redirect(&zim.dirents[3]), Err(ZimError::RedirectLoop { index: 4 })));
+        assert!(matches!(zim.resolve_redirect(&zim.dirents[5]), Err(ZimError::RedirectLoop { index: 5 })));
+        assert!(matches!(zim.resolve_redirect(&zim.dirents[6]), Err(ZimError::RedirectOutOfRange { index: 42 })));
+        assert!(matches!(zim.resolve_redirect_with_depth(&zim.dirents[2], 1), Err(ZimError::RedirectTooDeep { max_depth: 1 })));
+        assert_eq!(zim.resolve_redirect_with_depth(&zim.dirents[2], 2).unwrap().url, "a");
+    }
+
+    #[test]
+    fn test_main_entry() {
+        use crate::test_util::{blob, build_zim, redirect};
+        use crate::zimheader::NO_MAIN_PAGE;
+
+        let entries = [
+            blob('C', "index", "", b"home"),
+            redirect('C', "start", "", 0),
+            redirect('W', "mainPage", "", 1),
+        ];
+
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(build_zim(&entries, 1))).unwrap();
+        assert_eq!(zim.main_entry().unwrap().unwrap().url, "index");
+
+        // No main page in the header: fall back to W/mainPage
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(build_zim(&entries, NO_MAIN_PAGE))).unwrap();
+        assert_eq!(zim.header.main_page_index(), None);
+        assert_eq!(zim.main_entry().unwrap().unwrap().url, "index");
+
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(build_zim(&entries[..2], NO_MAIN_PAGE))).unwrap();
+        assert!(zim.main_entry().unwrap().is_none());
+
+        let zim = ZimFile::parse_bytes(&mut Cursor::new(build_zim(&entries, 7))).unwrap();
+        assert!(matches!(zim.main_entry(), Err(ZimError::OutOfBounds { index: 7, .. })));
     }
 }
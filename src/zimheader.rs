@@ -1,9 +1,30 @@
-use std::io::Read;
+use std::io::{Read, Write};
+use crate::error::{Structure, ZimError};
+use crate::namespace::NamespaceScheme;
 
 pub const ZIM_MAGIC_NUMBER: u32 = 0x044d495a;
 pub const HEADER_SIZE: usize = 80;
+/// Size of old headers, which end before the checksum position.
+const PRE_CHECKSUM_HEADER_SIZE: usize = 72;
+/// Value of `main_page` (and `layout_page`) when the archive has none.
+pub const NO_MAIN_PAGE: u32 = 0xffffffff;
 
-#[derive(Debug)]
+/// Major versions that can be read. 5 and 6 share the same layout, 6 added extended
+/// clusters (64-bit blob offsets), which are read for both.
+pub const SUPPORTED_MAJOR_VERSIONS: [u16; 2] = [5, 6];
+/// Latest known minor version of 6.x. Minor versions don't change the layout in an
+/// incompatible way, so newer ones are read as well:
+/// * 0: content spread over the legacy namespaces
+/// * 1: new namespace scheme, see `NamespaceScheme`. The title pointer list in the
+///   header is optional from then on, see `has_title_index`.
+/// * 2 and 3: no layout change, read exactly like 1.
+///
+/// Independently of the version, old headers end before the checksum position
+/// (see `has_checksum`), those archives have no checksum.
+pub const LATEST_MINOR_VERSION: u16 = 3;
+
+/// The fixed-size header at the start of every zim file.
+#[derive(Debug, PartialEq, Clone)]
 pub struct ZimHeader {
     pub magic_number: u32,
     pub major_version: u16,
@@ -21,17 +42,20 @@ pub struct ZimHeader {
 }
 
 impl ZimHeader {
-    pub fn parse_header(reader: &mut impl Read) -> Result<ZimHeader, String> {
+    pub fn parse_header(reader: &mut impl Read) -> Result<ZimHeader, ZimError> {
         let mut buffer = [0u8; HEADER_SIZE];
-        reader.read_exact(&mut buffer).map_err(|e| e.to_string())?;
+        reader.read_exact(&mut buffer).map_err(|e| ZimError::io(Structure::Header, e))?;
 
         let magic_number = u32::from_le_bytes(buffer[0..4].try_into().unwrap());
         if magic_number != ZIM_MAGIC_NUMBER {
-            return Err("Invalid magic number".to_string());
+            return Err(ZimError::InvalidMagicNumber { found: magic_number });
         }
 
         let major_version = u16::from_le_bytes(buffer[4..6].try_into().unwrap());
         let minor_version = u16::from_le_bytes(buffer[6..8].try_into().unwrap());
+        if !SUPPORTED_MAJOR_VERSIONS.contains(&major_version) {
+            return Err(ZimError::UnsupportedVersion { major: major_version, minor: minor_version });
+        }
         
         let mut uuid = [0u8; 16];
         uuid.copy_from_slice(&buffer[8..24]);
@@ -44,7 +68,13 @@ impl ZimHeader {
         let mime_list_pos = u64::from_le_bytes(buffer[56..64].try_into().unwrap());
         let main_page = u32::from_le_bytes(buffer[64..68].try_into().unwrap());
         let layout_page = u32::from_le_bytes(buffer[68..72].try_into().unwrap());
-        let checksum_pos = u64::from_le_bytes(buffer[72..80].try_into().unwrap());
+        // The header ends where the mime list starts. Old headers stop before the checksum
+        // position, the bytes read there already belong to the mime list.
+        let checksum_pos = if mime_list_pos >= HEADER_SIZE as u64 {
+            u64::from_le_bytes(buffer[72..80].try_into().unwrap())
+        } else {
+            0
+        };
 
         let header = ZimHeader {
             magic_number,
@@ -63,4 +93,168 @@ impl ZimHeader {
         };
         Ok(header)
     }
-}
\ No newline at end of file
+
+    /// Inverse of `parse_header`.
+    ///
+    /// Old headers, with the mime list starting before `HEADER_SIZE`, are written as the
+    /// 72 bytes preceding the checksum position, so the mime list isn't overwritten.
+    /// Their `checksum_pos` must be 0, and the mime list can't start inside the header.
+    pub fn write_to(&self, mut writer: impl Write) -> Result<(), ZimError> {
+        writer.write_all(&self.to_bytes()?).map_err(|e| ZimError::io(Structure::Header, e))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ZimError> {
+        let invalid = |field| ZimError::InvalidField { structure: Structure::Header, field };
+        let has_checksum_field = self.mime_list_pos >= HEADER_SIZE as u64;
+        if self.mime_list_pos < PRE_CHECKSUM_HEADER_SIZE as u64 {
+            return Err(invalid("mime_list_pos"));
+        }
+        if !has_checksum_field && self.checksum_pos != 0 {
+            return Err(invalid("checksum_pos"));
+        }
+
+        let mut buffer = Vec::with_capacity(HEADER_SIZE);
+        buffer.extend_from_slice(&self.magic_number.to_le_bytes());
+        buffer.extend_from_slice(&self.major_version.to_le_bytes());
+        buffer.extend_from_slice(&self.minor_version.to_le_bytes());
+        buffer.extend_from_slice(&self.uuid);
+        buffer.extend_from_slice(&self.article_count.to_le_bytes());
+        buffer.extend_from_slice(&self.cluster_count.to_le_bytes());
+        buffer.extend_from_slice(&self.path_ptr_pos.to_le_bytes());
+        buffer.extend_from_slice(&self.title_idx_pos.to_le_bytes());
+        buffer.extend_from_slice(&self.cluster_ptr_pos.to_le_bytes());
+        buffer.extend_from_slice(&self.mime_list_pos.to_le_bytes());
+        buffer.extend_from_slice(&self.main_page.to_le_bytes());
+        buffer.extend_from_slice(&self.layout_page.to_le_bytes());
+        if has_checksum_field {
+            buffer.extend_from_slice(&self.checksum_pos.to_le_bytes());
+        }
+        Ok(buffer)
+    }
+
+    /// Whether the header points to a title pointer list.
+    /// Archives without one leave the position at 0 or 0xffffffffffffffff.
+    pub fn has_title_index(&self) -> bool {
+        self.title_idx_pos != 0 && self.title_idx_pos != u64::MAX
+    }
+
+    pub fn namespace_scheme(&self) -> NamespaceScheme {
+        NamespaceScheme::from_header(self)
+    }
+
+    /// Whether the file ends with an MD5 checksum, which headers of old archives don't point to.
+    pub fn has_checksum(&self) -> bool {
+        self.checksum_pos >= HEADER_SIZE as u64
+    }
+
+    /// Index of the main page dirent, `None` if the header holds the "no main page" sentinel.
+    pub fn main_page_index(&self) -> Option<u32> {
+        if self.main_page == NO_MAIN_PAGE {
+            None
+        } else {
+            Some(self.main_page)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::test_util::{blob, build_zim, remove_checksum, set_version, Rng};
+
+    fn parse_with_version(major_version: u16, minor_version: u16) -> Result<ZimHeader, ZimError> {
+        let mut data = build_zim(&[], 0);
+        set_version(&mut data, major_version, minor_version);
+        ZimHeader::parse_header(&mut Cursor::new(data))
+    }
+
+    #[test]
+    fn test_supported_versions() {
+        assert!(parse_with_version(5, 0).is_ok());
+        assert!(parse_with_version(6, 0).is_ok());
+        assert!(parse_with_version(6, LATEST_MINOR_VERSION).is_ok());
+        // Newer minor versions stay compatible
+        assert!(parse_with_version(6, LATEST_MINOR_VERSION + 1).is_ok());
+    }
+
+    #[test]
+    fn test_unsupported_versions() {
+        let err = parse_with_version(7, 0).unwrap_err();
+        assert!(matches!(err, ZimError::UnsupportedVersion { major: 7, minor: 0 }));
+        assert_eq!(err.to_string(), "Unsupported zim version: 7.0");
+        assert!(matches!(parse_with_version(4, 2), Err(ZimError::UnsupportedVersion { major: 4, minor: 2 })));
+        assert!(matches!(parse_with_version(0, 0), Err(ZimError::UnsupportedVersion { .. })));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = Rng::new(0x5a1d);
+        for _ in 0..200 {
+            let header = ZimHeader {
+                magic_number: ZIM_MAGIC_NUMBER,
+                major_version: SUPPORTED_MAJOR_VERSIONS[rng.below(2) as usize],
+                minor_version: rng.below(5) as u16,
+                uuid: rng.bytes(16).try_into().unwrap(),
+                article_count: rng.next_u64() as u32,
+                cluster_count: rng.next_u64() as u32,
+                path_ptr_pos: rng.next_u64(),
+                title_idx_pos: rng.next_u64(),
+                cluster_ptr_pos: rng.next_u64(),
+                mime_list_pos: HEADER_SIZE as u64 + rng.below(1 << 40),
+                main_page: rng.next_u64() as u32,
+                layout_page: NO_MAIN_PAGE,
+                checksum_pos: rng.next_u64(),
+            };
+            // Old headers without checksum position, the mime list follows them
+            let old = rng.below(3) == 0;
+            let header = if old {
+                ZimHeader { mime_list_pos: PRE_CHECKSUM_HEADER_SIZE as u64, checksum_pos: 0, ..header }
+            } else {
+                header
+            };
+            let bytes = header.to_bytes().unwrap();
+            assert_eq!(bytes.len(), if old { PRE_CHECKSUM_HEADER_SIZE } else { HEADER_SIZE });
+            let mut file = bytes.clone();
+            file.extend_from_slice(b"text/html\0\0");
+            assert_eq!(ZimHeader::parse_header(&mut Cursor::new(&file)).unwrap(), header);
+
+            let mut written = Vec::new();
+            header.write_to(&mut written).unwrap();
+            assert_eq!(written, bytes);
+        }
+    }
+
+    #[test]
+    fn test_header_without_checksum() {
+        let mut data = build_zim(&[], 0);
+        let header = ZimHeader::parse_header(&mut Cursor::new(&data)).unwrap();
+        assert!(header.has_checksum());
+
+        // A 72 bytes header, directly followed by the mime list
+        data[56..64].copy_from_slice(&72u64.to_le_bytes());
+        let header = ZimHeader::parse_header(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(header.checksum_pos, 0);
+        assert!(!header.has_checksum());
+
+        let invalid = |header: ZimHeader| match header.to_bytes() {
+            Err(ZimError::InvalidField { structure: Structure::Header, field }) => field,
+            other => panic!("Expected an invalid field, got {:?}", other),
+        };
+        assert_eq!(invalid(ZimHeader { checksum_pos: 100, ..header.clone() }), "checksum_pos");
+        assert_eq!(invalid(ZimHeader { mime_list_pos: 64, ..header }), "mime_list_pos");
+    }
+
+    #[test]
+    fn test_patch_header_without_checksum() {
+        let mut data = remove_checksum(&build_zim(&[blob('C', "a", "", b"x")], NO_MAIN_PAGE));
+        let mut header = ZimHeader::parse_header(&mut Cursor::new(&data)).unwrap();
+        header.main_page = 0;
+        header.write_to(&mut data[..]).unwrap();
+
+        let zim = crate::ZimFile::parse_bytes(&mut Cursor::new(data)).unwrap();
+        assert_eq!(zim.header, header);
+        assert_eq!(zim.mime_types, vec!["text/html"]);
+        assert_eq!(zim.main_entry().unwrap().unwrap().url, "a");
+    }
+}